@@ -1,5 +1,8 @@
 // use std::time::Instant;
-#[derive(Debug)]
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Negation, // ! true now its false and vice versa
     Conjunction, // &
@@ -37,6 +40,7 @@ fn parse_formula(formula: &str) -> Node{
 
     for c in formula.chars() {
         match c {
+            'A'..='Z' => tree.push(Value(c)),
             '0' => tree.push(Bool(false)),
             '1' => tree.push(Bool(true)),
             '!' =>{
@@ -76,16 +80,17 @@ fn parse_formula(formula: &str) -> Node{
 }
 
 
-fn evaluate(node: &Node) -> bool {
+fn evaluate(node: &Node, assignment: &Assignment) -> bool {
     match node {
         Node::Bool(val) => *val,
+        Node::Value(c) => assignment.value(*c),
         Node::UnaryExpr{ op: _, child } => {
-            let val = evaluate(child);
+            let val = evaluate(child, assignment);
             !val
         }
         Node::BinaryExpr{ op, lhs, rhs} => {
-            let left = evaluate(lhs);
-            let right = evaluate(rhs);
+            let left = evaluate(lhs, assignment);
+            let right = evaluate(rhs, assignment);
             let res: bool;
             match op {
                 Conjunction => res = left & right,
@@ -98,7 +103,6 @@ fn evaluate(node: &Node) -> bool {
             }
             res
         }
-        Node::Value(_val) => panic!("There should not be any char at this momment"),
     }
 }
 
@@ -114,6 +118,9 @@ fn parse_formula_char(formula: &str) ->  Vec<char> {
                     used_char.push(c);
                 }
             }
+            '0' | '1' => {
+                continue;
+            }
             '!' =>{
                 continue;
             }
@@ -138,55 +145,394 @@ fn parse_formula_char(formula: &str) ->  Vec<char> {
     used_char
 }
 
-fn give_value_to_char(current_line: i64, formula: &str, used_char: &[char]) -> Node {
-    let mut changed_formula: String = formula.to_string();
-    let base: i64 = 2;
-    let n = used_char.len();
+//================================ STREAMING TRUTH TABLE ================================
+//=======================================================================================
 
-    for i in 0..n {
-        let pow = base.pow((n - i - 1) as u32);
-        let val = (current_line / pow) % 2;
-        changed_formula = changed_formula.replace(used_char[i], &val.to_string());
-        print!("| {val} ");
+// One row of the table: its index and the value given to every letter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Assignment {
+    pub row: u64,
+    values: [bool; 26],
+}
+
+impl Assignment {
+    // The first char of used_char is the most significant bit of the row
+    fn new(row: u64, used_char: &[char]) -> Self {
+        let mut values = [false; 26];
+        let n = used_char.len();
+        for (i, c) in used_char.iter().enumerate() {
+            values[letter_index(*c)] = (row >> (n - i - 1)) & 1 == 1;
+        }
+        Assignment { row, values }
     }
 
-    parse_formula(changed_formula.as_str())
+    pub fn value(&self, c: char) -> bool {
+        self.values[letter_index(c)]
+    }
+}
+
+fn letter_index(c: char) -> usize {
+    (c as u8 - b'A') as usize
+}
+
+fn gray_code(n: u64) -> u64 {
+    n ^ (n >> 1)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowOrder {
+    Binary, // 000, 001, 010, 011...
+    Gray, // 000, 001, 011, 010... only one input flips between rows (K-map order)
+}
+
+#[derive(Debug)]
+enum Gate {
+    Leaf(char),
+    Const(bool),
+    Unary(usize),
+    Binary(Operator, usize, usize),
+}
+
+// Flattened formula where every gate keeps its value and knows its parent,
+// so flipping one input only recomputes the path from its leaves to the root
+#[derive(Debug)]
+struct Circuit {
+    gates: Vec<Gate>,
+    parents: Vec<Option<usize>>,
+    values: Vec<bool>,
+    leaves: [Vec<usize>; 26],
 }
 
-fn print_and_resolve(used_char: &Vec<char>, formula: &str) {
-    for val in used_char {
-        print!("| {val} ");
-        
+impl Circuit {
+    fn new(root: &Node) -> Self {
+        let mut circuit = Circuit { gates: Vec::new(), parents: Vec::new(), values: Vec::new(), leaves: Default::default() };
+        circuit.add(root);
+        circuit
     }
-    println!("| = |");
-    for _i in 0..=used_char.len(){
-        print!("|---");
+
+    // Children are pushed before their parent, the root ends up last
+    fn add(&mut self, node: &Node) -> usize {
+        let gate = match node {
+            Node::Value(c) => {
+                self.leaves[letter_index(*c)].push(self.gates.len());
+                Gate::Leaf(*c)
+            }
+            Node::Bool(val) => Gate::Const(*val),
+            Node::UnaryExpr { op: _, child } => Gate::Unary(self.add(child)),
+            Node::BinaryExpr { op, lhs, rhs } => {
+                let lhs = self.add(lhs);
+                let rhs = self.add(rhs);
+                Gate::Binary(*op, lhs, rhs)
+            }
+        };
+        let id = self.gates.len();
+        match gate {
+            Gate::Unary(child) => self.parents[child] = Some(id),
+            Gate::Binary(_, lhs, rhs) => {
+                self.parents[lhs] = Some(id);
+                self.parents[rhs] = Some(id);
+            }
+            _ => {}
+        }
+        self.gates.push(gate);
+        self.parents.push(None);
+        self.values.push(false);
+        id
     }
-    println!("|");
-    let base = 2i64;
-    let iterations = base.pow((used_char.len()) as u32);
-    for i in 0..iterations {
-        let node = give_value_to_char(i, formula, used_char);
-        let val = evaluate(&node);
-        if val {
-            println!("| 1 |");
-        } else {
-            println!("| 0 |");
+
+    fn compute(&self, id: usize, assignment: &Assignment) -> bool {
+        match self.gates[id] {
+            Gate::Leaf(c) => assignment.value(c),
+            Gate::Const(val) => val,
+            Gate::Unary(child) => !self.values[child],
+            Gate::Binary(op, lhs, rhs) => {
+                let left = self.values[lhs];
+                let right = self.values[rhs];
+                match op {
+                    Conjunction => left & right,
+                    Disjunction => left | right,
+                    ExclusiveDisjunction => left ^ right,
+                    MaterialCondition => !left | right,
+                    LogicalEquivalence => !(left ^ right),
+                    Negation => panic!("Should not enter here"),
+                }
+            }
+        }
+    }
+
+    fn evaluate_all(&mut self, assignment: &Assignment) -> bool {
+        for id in 0..self.gates.len() {
+            self.values[id] = self.compute(id, assignment);
+        }
+        self.root_value()
+    }
+
+    // Walk up from every leaf of `c`, stopping as soon as a gate keeps its old value
+    fn flip(&mut self, c: char, assignment: &Assignment) -> bool {
+        for i in 0..self.leaves[letter_index(c)].len() {
+            let mut id = self.leaves[letter_index(c)][i];
+            loop {
+                let val = self.compute(id, assignment);
+                if val == self.values[id] {
+                    break;
+                }
+                self.values[id] = val;
+                match self.parents[id] {
+                    Some(parent) => id = parent,
+                    None => break,
+                }
+            }
+        }
+        self.root_value()
+    }
+
+    fn root_value(&self) -> bool {
+        *self.values.last().expect("Empty circuit")
+    }
+}
+
+// Lazy truth table, rows are only evaluated when the iterator asks for them
+pub struct TruthTable {
+    root: Node,
+    used_char: Vec<char>,
+    next_row: u64,
+    end_row: u64,
+    only: Option<bool>,
+    order: RowOrder,
+    circuit: Option<Circuit>,
+    // Position of the last row the circuit holds the values of
+    synced: Option<u64>,
+}
+
+impl TruthTable {
+    pub fn new(formula: &str) -> Self {
+        let used_char = parse_formula_char(formula);
+        assert!(used_char.len() < 64, "Too many variables for a truth table");
+        let end_row = 1u64 << used_char.len();
+        TruthTable {
+            root: parse_formula(formula),
+            used_char,
+            next_row: 0,
+            end_row,
+            only: None,
+            order: RowOrder::Binary,
+            circuit: None,
+            synced: None,
+        }
+    }
+
+    pub fn used_char(&self) -> &[char] {
+        &self.used_char
+    }
+
+    pub fn rows(&self) -> u64 {
+        1u64 << self.used_char.len()
+    }
+
+    // Skip every row before `row`, so an interrupted export can pick up where it stopped
+        pub fn resume_from(mut self, row: u64) -> Self {
+        self.next_row = self.next_row.max(row).min(self.end_row);
+        self
+    }
+
+    // Only give back the rows where the formula is `value`
+    pub fn only(mut self, value: bool) -> Self {
+        self.only = Some(value);
+        self
+    }
+
+    // Visit the rows in reflected Gray order, positions given to resume_from and shard
+    // are then positions in that order and not binary row numbers
+    pub fn gray_order(mut self) -> Self {
+        self.order = RowOrder::Gray;
+        self.circuit = Some(Circuit::new(&self.root));
+        self
+    }
+
+    // Keep the `index`-th of `count` contiguous slices of the rows, one per worker
+    pub fn shard(mut self, index: u64, count: u64) -> Self {
+        assert!(index < count, "Shard index out of range");
+        let rows = self.rows() as u128;
+        let start = (rows * index as u128 / count as u128) as u64;
+        let end = (rows * (index as u128 + 1) / count as u128) as u64;
+        self.end_row = self.end_row.min(end);
+        self.next_row = self.next_row.max(start).min(self.end_row);
+        self
+    }
+}
+
+impl Iterator for TruthTable {
+    type Item = (Assignment, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_row < self.end_row {
+            let position = self.next_row;
+            self.next_row += 1;
+            let val;
+            let assignment;
+            match (self.order, self.circuit.as_mut()) {
+                (RowOrder::Gray, Some(circuit)) => {
+                    assignment = Assignment::new(gray_code(position), &self.used_char);
+                    val = if position > 0 && self.synced == Some(position - 1) {
+                        // gray_code(i) and gray_code(i - 1) differ by the lowest set bit of i
+                        let bit = position.trailing_zeros() as usize;
+                        let c = self.used_char[self.used_char.len() - bit - 1];
+                        circuit.flip(c, &assignment)
+                    } else {
+                        circuit.evaluate_all(&assignment)
+                    };
+                    self.synced = Some(position);
+                }
+                _ => {
+                    assignment = Assignment::new(position, &self.used_char);
+                    val = evaluate(&self.root, &assignment);
+                }
+            }
+            if self.only.is_none_or(|only| only == val) {
+                return Some((assignment, val));
+            }
         }
+        None
     }
-    
 }
 
+//=======================================================================================
+
+
+// `outputs` are the labels of the result columns, "=" for a single formula
+fn write_header<W: Write>(out: &mut W, used_char: &[char], outputs: &[char]) -> io::Result<()> {
+    for val in used_char.iter().chain(outputs) {
+        write!(out, "| {val} ")?;
+    }
+    writeln!(out, "|")?;
+    for _i in 0..used_char.len() + outputs.len(){
+        write!(out, "|---")?;
+    }
+    writeln!(out, "|")
+}
+
+fn write_row<W: Write>(out: &mut W, used_char: &[char], assignment: &Assignment, vals: &[bool]) -> io::Result<()> {
+    for c in used_char {
+        write!(out, "| {} ", assignment.value(*c) as u8)?;
+    }
+    for val in vals {
+        write!(out, "| {} ", *val as u8)?;
+    }
+    writeln!(out, "|")
+}
+
+fn write_truth_table<W: Write>(table: TruthTable, out: &mut W) -> io::Result<()> {
+    let used_char = table.used_char().to_vec();
+    write_header(out, &used_char, &['='])?;
+    for (assignment, val) in table {
+        write_row(out, &used_char, &assignment, &[val])?;
+    }
+    Ok(())
+}
+
+// Rows go straight to the file, so the table never has to fit in memory
+fn export_truth_table(table: TruthTable, path: &str) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_truth_table(table, &mut out)?;
+    out.flush()
+}
 
 fn print_truth_table(formula: &str){
-    let used_char = parse_formula_char(formula);
-    print_and_resolve(&used_char, formula);
+    write_truth_table(TruthTable::new(formula), &mut io::stdout().lock()).expect("Could not print the truth table");
 }
 
+// Same table, but rows come in K-map adjacency order
+fn print_gray_truth_table(formula: &str){
+    write_truth_table(TruthTable::new(formula).gray_order(), &mut io::stdout().lock()).expect("Could not print the truth table");
+}
+
+
+//==================================== TABLE DIFF =======================================
+//=======================================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TableDiff {
+    pub became_true: u64, // f is 0 and g is 1
+    pub became_false: u64, // f is 1 and g is 0
+}
+
+// Variables of f first, then the ones only g uses
+fn union_char(f: &str, g: &str) -> Vec<char> {
+    let mut used_char = parse_formula_char(f);
+    for c in parse_formula_char(g) {
+        if !used_char.contains(&c) {
+            used_char.push(c);
+        }
+    }
+    used_char
+}
+
+fn write_diff<W: Write>(f: &str, g: &str, out: &mut W) -> io::Result<TableDiff> {
+    let used_char = union_char(f, g);
+    assert!(used_char.len() < 64, "Too many variables for a truth table");
+    let (f_root, g_root) = (parse_formula(f), parse_formula(g));
+    let mut diff = TableDiff::default();
+
+    write_header(out, &used_char, &['f', 'g'])?;
+    for row in 0..1u64 << used_char.len() {
+        let assignment = Assignment::new(row, &used_char);
+        let (f_val, g_val) = (evaluate(&f_root, &assignment), evaluate(&g_root, &assignment));
+        if f_val == g_val {
+            continue;
+        }
+        if g_val {
+            diff.became_true += 1;
+        } else {
+            diff.became_false += 1;
+        }
+        write_row(out, &used_char, &assignment, &[f_val, g_val])?;
+    }
+    writeln!(out, "{} row(s) became true, {} row(s) became false", diff.became_true, diff.became_false)?;
+    Ok(diff)
+}
+
+// Only prints the rows where f and g disagree
+fn diff_tables(f: &str, g: &str) -> TableDiff {
+    write_diff(f, g, &mut io::stdout().lock()).expect("Could not print the diff")
+}
+
+//=======================================================================================
+
 
 fn main() {
     // let start = Instant::now();
     print_truth_table("AB&C|DE&^FG|^HI&^");
+    println!();
+    // Second half of the table, only the rows where it holds
+    let table = TruthTable::new("AB&C|").only(true).shard(1, 2);
+    write_truth_table(table, &mut io::stdout().lock()).expect("Could not print the truth table");
+    println!();
+    print_gray_truth_table("AB^C=");
+    println!();
+    // Picking up at some row gives back the tail of a full run, in both orders
+    let full: Vec<(Assignment, bool)> = TruthTable::new("AB&C|D^").collect();
+    let resumed: Vec<(Assignment, bool)> = TruthTable::new("AB&C|D^").resume_from(5).collect();
+    assert!(resumed == full[5..], "resume_from did not give the tail of the table");
+    let full: Vec<(Assignment, bool)> = TruthTable::new("AB&C|D^").gray_order().collect();
+    let resumed: Vec<(Assignment, bool)> = TruthTable::new("AB&C|D^").gray_order().resume_from(5).collect();
+    assert!(resumed == full[5..], "resume_from did not give the tail of the Gray table");
+    // The exported file holds what write_truth_table gives in memory
+    let path = std::env::temp_dir().join("ex04_truth_table.md");
+    let path = path.to_str().expect("Temp directory is not valid UTF-8");
+    export_truth_table(TruthTable::new("AB&C|D^").resume_from(5), path).expect("Could not export the truth table");
+    let mut expected = Vec::new();
+    write_truth_table(TruthTable::new("AB&C|D^").resume_from(5), &mut expected).expect("Could not write the truth table");
+    assert!(std::fs::read(path).expect("Could not read the export back") == expected, "Export does not match the table");
+    std::fs::remove_file(path).expect("Could not remove the export");
+    println!("{} rows exported from row 5", expected.iter().filter(|byte| **byte == b'\n').count() - 2);
+    // 11 rows exported from row 5
+    diff_tables("AB&C|", "AC|BC|&");
+    // 0 row(s) became true, 0 row(s) became false
+    println!();
+    diff_tables("AB&C|", "AB|C&");
+    // 0 row(s) became true, 2 row(s) became false
+    println!();
+    print_truth_table("A1&B|");
     // let duration = start.elapsed();
     // println!("Took {:?}", duration);
 }
@@ -39,6 +39,8 @@ fn parse_formula(formula: &str) -> Node{
             'A'..='Z' => {
                 tree.push(Value(c));
             }
+            '0' => tree.push(Bool(false)),
+            '1' => tree.push(Bool(true)),
             '!' =>{
                 let child = Box::new(tree.pop().expect("Missing value for !"));
                 tree.push(UnaryExpr {op: Negation, child });
@@ -75,209 +77,348 @@ fn parse_formula(formula: &str) -> Node{
     tree.pop().unwrap()
 }
 
-fn equivalence(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::LogicalEquivalence, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(equivalence(*lhs));
-            let rhs = Box::new(equivalence(*rhs));
-
-            // Contruct the Material condition of lhs with rhs
-            let left_and = Node::BinaryExpr {
-                op: Operator::MaterialCondition,
-                lhs: lhs.clone(),
-                rhs: rhs.clone(),
-            };
-
-            // Contruct the Material condition of rhs with lhs
-            let right_and = Node::BinaryExpr {
-                op: Operator::MaterialCondition,
-                lhs: rhs.clone(),
-                rhs: lhs.clone(),
-            };
+fn binary(op: Operator, lhs: Node, rhs: Node) -> Node {
+    Node::BinaryExpr { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+}
 
-            // Return the Conjunction of the left and right conditions
-            Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: Box::new(left_and),
-                rhs: Box::new(right_and),
+// Push the negations down to the letters in a single walk of the tree.
+// `positive` is false when an odd number of negations sits above the node,
+// so instead of building ¬X and rewriting it later the negated form is built directly.
+// Each arm writes down the laws it applies, on the subformula standing in the tree at
+// that point (¬node when the polarity is negative), before its children rewrite further
+fn nnf(node: &Node, positive: bool, proof: &mut Proof) -> Node {
+    let not = |n: &Node| Node::UnaryExpr { op: Negation, child: Box::new(n.clone()) };
+    let and = |l: &Node, r: &Node| binary(Conjunction, l.clone(), r.clone());
+    let or = |l: &Node, r: &Node| binary(Disjunction, l.clone(), r.clone());
+    let imp = |l: &Node, r: &Node| binary(MaterialCondition, l.clone(), r.clone());
+    match node {
+        Node::Value(_) | Node::Bool(_) => {
+            if positive {
+                node.clone()
+            } else {
+                not(node)
             }
         }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(equivalence(*lhs)),
-            rhs: Box::new(equivalence(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(equivalence(*child)),
+        // Double negation: ¬¬A ≡ A
+        Node::UnaryExpr { op: _, child } => {
+            if !positive {
+                proof.record_at("Double negation", &[], || (not(&not(child)), (**child).clone()));
+            }
+            nnf_at(&[], child, !positive, proof)
+        }
+        Node::BinaryExpr { op, lhs: a, rhs: b } => match (op, positive) {
+            (Operator::Conjunction, true) => binary(Conjunction, nnf_at(&[1], a, true, proof), nnf_at(&[2], b, true, proof)),
+            (Operator::Disjunction, true) => binary(Disjunction, nnf_at(&[1], a, true, proof), nnf_at(&[2], b, true, proof)),
+            // De Morgan: ¬(A ∧ B) ≡ ¬A ∨ ¬B
+            (Operator::Conjunction, false) => {
+                proof.record_at("De Morgan", &[], || (not(&and(a, b)), or(&not(a), &not(b))));
+                binary(Disjunction, nnf_at(&[1], a, false, proof), nnf_at(&[2], b, false, proof))
+            }
+            // De Morgan: ¬(A ∨ B) ≡ ¬A ∧ ¬B
+            (Operator::Disjunction, false) => {
+                proof.record_at("De Morgan", &[], || (not(&or(a, b)), and(&not(a), &not(b))));
+                binary(Conjunction, nnf_at(&[1], a, false, proof), nnf_at(&[2], b, false, proof))
+            }
+            // Material condition: A → B ≡ ¬A ∨ B
+            (Operator::MaterialCondition, true) => {
+                proof.record_at("Material condition", &[], || (imp(a, b), or(&not(a), b)));
+                binary(Disjunction, nnf_at(&[1], a, false, proof), nnf_at(&[2], b, true, proof))
+            }
+            // ¬(A → B) ≡ ¬(¬A ∨ B) ≡ ¬¬A ∧ ¬B ≡ A ∧ ¬B
+            (Operator::MaterialCondition, false) => {
+                proof.record_at("Material condition", &[1], || (imp(a, b), or(&not(a), b)));
+                proof.record_at("De Morgan", &[], || (not(&or(&not(a), b)), and(&not(&not(a)), &not(b))));
+                proof.record_at("Double negation", &[1], || (not(&not(a)), (**a).clone()));
+                binary(Conjunction, nnf_at(&[1], a, true, proof), nnf_at(&[2], b, false, proof))
+            }
+            // A ⊕ B ≡ (A ∧ ¬B) ∨ (¬A ∧ B)
+            (Operator::ExclusiveDisjunction, true) => {
+                proof.record_at("XOR elimination", &[], || (node.clone(), or(&and(a, &not(b)), &and(&not(a), b))));
+                binary(
+                    Disjunction,
+                    binary(Conjunction, nnf_at(&[1, 1], a, true, proof), nnf_at(&[1, 2], b, false, proof)),
+                    binary(Conjunction, nnf_at(&[2, 1], a, false, proof), nnf_at(&[2, 2], b, true, proof)),
+                )
+            }
+            // ¬(A ⊕ B) ≡ ¬(A ∧ ¬B) ∧ ¬(¬A ∧ B) ≡ (¬A ∨ B) ∧ (A ∨ ¬B)
+            (Operator::ExclusiveDisjunction, false) => {
+                let expanded = || or(&and(a, &not(b)), &and(&not(a), b));
+                proof.record_at("XOR elimination", &[1], || (node.clone(), expanded()));
+                proof.record_at("De Morgan", &[], || (not(&expanded()), and(&not(&and(a, &not(b))), &not(&and(&not(a), b)))));
+                proof.record_at("De Morgan", &[1], || (not(&and(a, &not(b))), or(&not(a), &not(&not(b)))));
+                proof.record_at("De Morgan", &[2], || (not(&and(&not(a), b)), or(&not(&not(a)), &not(b))));
+                proof.record_at("Double negation", &[1, 2], || (not(&not(b)), (**b).clone()));
+                proof.record_at("Double negation", &[2, 1], || (not(&not(a)), (**a).clone()));
+                binary(
+                    Conjunction,
+                    binary(Disjunction, nnf_at(&[1, 1], a, false, proof), nnf_at(&[1, 2], b, true, proof)),
+                    binary(Disjunction, nnf_at(&[2, 1], a, true, proof), nnf_at(&[2, 2], b, false, proof)),
+                )
+            }
+            // A ↔ B ≡ (A → B) ∧ (B → A) ≡ (¬A ∨ B) ∧ (¬B ∨ A)
+            (Operator::LogicalEquivalence, true) => {
+                proof.record_at("Equivalence", &[], || (node.clone(), and(&imp(a, b), &imp(b, a))));
+                proof.record_at("Material condition", &[1], || (imp(a, b), or(&not(a), b)));
+                proof.record_at("Material condition", &[2], || (imp(b, a), or(&not(b), a)));
+                binary(
+                    Conjunction,
+                    binary(Disjunction, nnf_at(&[1, 1], a, false, proof), nnf_at(&[1, 2], b, true, proof)),
+                    binary(Disjunction, nnf_at(&[2, 1], b, false, proof), nnf_at(&[2, 2], a, true, proof)),
+                )
+            }
+            // ¬(A ↔ B) ≡ ¬((¬A ∨ B) ∧ (¬B ∨ A)) ≡ (A ∧ ¬B) ∨ (B ∧ ¬A)
+            (Operator::LogicalEquivalence, false) => {
+                let expanded = || and(&or(&not(a), b), &or(&not(b), a));
+                proof.record_at("Equivalence", &[1], || (node.clone(), and(&imp(a, b), &imp(b, a))));
+                proof.record_at("Material condition", &[1, 1], || (imp(a, b), or(&not(a), b)));
+                proof.record_at("Material condition", &[1, 2], || (imp(b, a), or(&not(b), a)));
+                proof.record_at("De Morgan", &[], || (not(&expanded()), or(&not(&or(&not(a), b)), &not(&or(&not(b), a)))));
+                proof.record_at("De Morgan", &[1], || (not(&or(&not(a), b)), and(&not(&not(a)), &not(b))));
+                proof.record_at("De Morgan", &[2], || (not(&or(&not(b), a)), and(&not(&not(b)), &not(a))));
+                proof.record_at("Double negation", &[1, 1], || (not(&not(a)), (**a).clone()));
+                proof.record_at("Double negation", &[2, 1], || (not(&not(b)), (**b).clone()));
+                binary(
+                    Disjunction,
+                    binary(Conjunction, nnf_at(&[1, 1], a, true, proof), nnf_at(&[1, 2], b, false, proof)),
+                    binary(Conjunction, nnf_at(&[2, 1], b, true, proof), nnf_at(&[2, 2], a, false, proof)),
+                )
+            }
+            (Operator::Negation, _) => panic!("Should not enter here"),
         },
-        other => other,
     }
 }
 
+// `side` is where the child ends up in the result, relative to the current node
+fn nnf_at(side: &[u8], node: &Node, positive: bool, proof: &mut Proof) -> Node {
+    proof.path.extend_from_slice(side);
+    let node = nnf(node, positive, proof);
+    proof.path.truncate(proof.path.len() - side.len());
+    node
+}
 
+//=================================== DERIVATION LOG ====================================
+//=======================================================================================
 
-fn material_conditon(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::MaterialCondition, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(material_conditon(*lhs));
-            let rhs = Box::new(material_conditon(*rhs));
-
-            // Construct (¬lhs ∧ rhs)
-            Node::BinaryExpr {
-                op: Operator::Disjunction,
-                lhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: lhs,
-                }),
-                rhs,
-            }
-        }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(material_conditon(*lhs)),
-            rhs: Box::new(material_conditon(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(material_conditon(*child)),
-        },
-        other => other,
-    }
+// One law applied to the subformula found at `path` (1 is the lhs or the only child, 2 the rhs)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub law: &'static str,
+    pub path: Vec<u8>,
+    pub before: Node,
+    pub after: Node,
 }
 
+// nnf and simplify fill one of these, when it is disabled nothing is cloned or stored
+#[derive(Debug, Default)]
+pub struct Proof {
+    enabled: bool,
+    path: Vec<u8>,
+    pub steps: Vec<Step>,
+}
 
-fn remove_xor(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::ExclusiveDisjunction, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(remove_xor(*lhs));
-            let rhs = Box::new(remove_xor(*rhs));
-
-            // Construct (lhs ∧ ¬rhs)
-            let left_and = Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: lhs.clone(),
-                rhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: rhs.clone(),
-                }),
-            };
+impl Proof {
+    fn disabled() -> Self {
+        Proof::default()
+    }
 
-            // Construct (¬lhs ∧ rhs)
-            let right_and = Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: lhs,
-                }),
-                rhs,
-            };
+    fn enabled() -> Self {
+        Proof { enabled: true, ..Default::default() }
+    }
 
-            // Return (lhs ∧ ¬rhs) ∨ (¬lhs ∧ rhs)
-            Node::BinaryExpr {
-                op: Operator::Disjunction,
-                lhs: Box::new(left_and),
-                rhs: Box::new(right_and),
-            }
+    // Run a rewrite on a child, keeping track of where in the tree it is
+    fn descend(&mut self, side: u8, node: Node, rewrite: fn(Node, &mut Proof) -> Node) -> Box<Node> {
+        self.path.push(side);
+        let node = rewrite(node, self);
+        self.path.pop();
+        Box::new(node)
+    }
+
+    fn snapshot(&self, before: impl FnOnce() -> Node) -> Option<Node> {
+        if self.enabled { Some(before()) } else { None }
+    }
+
+    fn record(&mut self, law: &'static str, before: Option<Node>, after: Node) -> Node {
+        if let Some(before) = before {
+            self.steps.push(Step { law, path: self.path.clone(), before, after: after.clone() });
+        }
+        after
+    }
+
+    // `rel` is the position of the rewritten subformula below the current node,
+    // `step` gives the before and after and is only called when the proof is enabled
+    fn record_at(&mut self, law: &'static str, rel: &[u8], step: impl FnOnce() -> (Node, Node)) {
+        if !self.enabled {
+            return;
+        }
+        let (before, after) = step();
+        let mut path = self.path.clone();
+        path.extend_from_slice(rel);
+        self.steps.push(Step { law, path, before, after });
+    }
+
+    // Replays the steps from `start`, printing the whole formula after each one
+    fn print(&self, start: &Node) {
+        let mut current = start.clone();
+        println!("{:>4}. {}", 0, ast_to_rpn(&current));
+        for (i, step) in self.steps.iter().enumerate() {
+            let target = subtree_mut(&mut current, &step.path);
+            assert!(*target == step.before, "Step {} does not match the formula", i + 1);
+            *target = step.after.clone();
+            println!("{:>4}. {}", i + 1, ast_to_rpn(&current));
+            println!("      by {} at {}: {} ≡ {}", step.law, path_to_string(&step.path), ast_to_rpn(&step.before), ast_to_rpn(&step.after));
         }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(remove_xor(*lhs)),
-            rhs: Box::new(remove_xor(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(remove_xor(*child)),
-        },
-        other => other,
     }
 }
 
+fn subtree_mut<'a>(node: &'a mut Node, path: &[u8]) -> &'a mut Node {
+    match (path.first(), node) {
+        (None, node) => node,
+        (Some(1), Node::UnaryExpr { op: _, child }) => subtree_mut(child, &path[1..]),
+        (Some(1), Node::BinaryExpr { op: _, lhs, rhs: _ }) => subtree_mut(lhs, &path[1..]),
+        (Some(2), Node::BinaryExpr { op: _, lhs: _, rhs }) => subtree_mut(rhs, &path[1..]),
+        _ => panic!("Invalid path in the derivation"),
+    }
+}
 
-fn de_morgans_law(node: Node) -> Node{
+fn path_to_string(path: &[u8]) -> String {
+    if path.is_empty() {
+        return "root".to_string();
+    }
+    path.iter().map(|side| side.to_string()).collect::<Vec<String>>().join(".")
+}
+
+//=======================================================================================
+
+// Fold the constants away: A∧1 → A, A∨1 → 1, A⊕0 → A, 1→A → A...
+// Bottom-up, so afterwards either the whole formula is 0 or 1 or no constant is left
+fn simplify(node: Node, proof: &mut Proof) -> Node {
     match node {
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(de_morgans_law(*lhs)),
-            rhs: Box::new(de_morgans_law(*rhs)),
-        },
-        Node::UnaryExpr { op: Negation, child } =>{
-            match *child{
-                Node::BinaryExpr {op: Operator::Conjunction, lhs, rhs} => {
-                    Node::BinaryExpr {
-                        op: Operator::Disjunction,
-                        lhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*lhs),
-                        }),
-                        rhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*rhs),
-                        }),
-                    }
-                }
-                Node::BinaryExpr {op: Operator::Disjunction, lhs, rhs} => {
-                    Node::BinaryExpr {
-                        op: Operator::Conjunction,
-                        lhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*lhs),
-                        }),
-                        rhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*rhs),
-                        }),
-                    }
-                }
-                other => Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: Box::new(other),
+        Node::UnaryExpr { op, child } => {
+            let child = proof.descend(1, *child, simplify);
+            match *child {
+                Node::Bool(val) => {
+                    let before = proof.snapshot(|| Node::UnaryExpr { op: Negation, child: Box::new(Bool(val)) });
+                    proof.record("Constant evaluation", before, Bool(!val))
                 }
+                child => Node::UnaryExpr { op, child: Box::new(child) },
             }
         }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let lhs = proof.descend(1, *lhs, simplify);
+            let rhs = proof.descend(2, *rhs, simplify);
+            if !matches!(*lhs, Bool(_)) && !matches!(*rhs, Bool(_)) {
+                return Node::BinaryExpr { op, lhs, rhs };
+            }
+            let before = proof.snapshot(|| Node::BinaryExpr { op: op.clone(), lhs: lhs.clone(), rhs: rhs.clone() });
+            let negate = |node: Node| Node::UnaryExpr { op: Negation, child: Box::new(node) };
+            let (law, after) = match (op, *lhs, *rhs) {
+                (op, Bool(a), Bool(b)) => ("Constant evaluation", Bool(match op {
+                    Conjunction => a & b,
+                    Disjunction => a | b,
+                    ExclusiveDisjunction => a ^ b,
+                    MaterialCondition => !a | b,
+                    LogicalEquivalence => a == b,
+                    Negation => panic!("Should not enter here"),
+                })),
+                (Conjunction, Bool(true), x) | (Conjunction, x, Bool(true)) => ("Identity", x),
+                (Disjunction, Bool(false), x) | (Disjunction, x, Bool(false)) => ("Identity", x),
+                (ExclusiveDisjunction, Bool(false), x) | (ExclusiveDisjunction, x, Bool(false)) => ("Identity", x),
+                (LogicalEquivalence, Bool(true), x) | (LogicalEquivalence, x, Bool(true)) => ("Identity", x),
+                (MaterialCondition, Bool(true), x) => ("Identity", x),
+                (Conjunction, _, _) => ("Annihilation", Bool(false)),
+                (Disjunction, _, _) => ("Annihilation", Bool(true)),
+                (MaterialCondition, Bool(false), _) | (MaterialCondition, _, Bool(true)) => ("Annihilation", Bool(true)),
+                (MaterialCondition, x, _) => ("Complement", negate(x)),
+                (ExclusiveDisjunction, Bool(true), x) | (ExclusiveDisjunction, x, _) => ("Complement", negate(x)),
+                (LogicalEquivalence, Bool(false), x) | (LogicalEquivalence, x, _) => ("Complement", negate(x)),
+                (Negation, _, _) => panic!("Should not enter here"),
+            };
+            proof.record(law, before, after)
+        }
         other => other,
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XorMode {
+    Expand, // ^ and = become ∧ and ∨, plain NNF
+    Keep, // extended NNF, a negation flips ^ into = and = into ^
+    ExpandUnder(usize), // expand only when it adds at most that many nodes
+}
 
+fn node_count(node: &Node) -> usize {
+    match node {
+        Node::Value(_) | Node::Bool(_) => 1,
+        Node::UnaryExpr { op: _, child } => 1 + node_count(child),
+        Node::BinaryExpr { op: _, lhs, rhs } => 1 + node_count(lhs) + node_count(rhs),
+    }
+}
 
-// last step removing the double negations
-fn double_negation(node: Node) -> Node {
-     match node {
-        Node::UnaryExpr { op: Operator::Negation, child} => {
-            match *child {
-                Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: inner,
-                } => {
-                    *inner // Double negation: remove both
+// Both polarities of a node at once, (node, ¬node), in extended NNF.
+// Building them bottom-up lets ^ and = pick between the compact and the
+// expanded form knowing how big each one would be
+fn extended_nnf(node: &Node, mode: XorMode) -> (Node, Node) {
+    match node {
+        Node::Value(_) | Node::Bool(_) => {
+            (node.clone(), Node::UnaryExpr { op: Operator::Negation, child: Box::new(node.clone()) })
+        }
+        Node::UnaryExpr { op: _, child } => {
+            let (pos, neg) = extended_nnf(child, mode);
+            (neg, pos)
+        }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let (a, not_a) = extended_nnf(lhs, mode);
+            let (b, not_b) = extended_nnf(rhs, mode);
+            match op {
+                Operator::Conjunction => (binary(Conjunction, a, b), binary(Disjunction, not_a, not_b)),
+                Operator::Disjunction => (binary(Disjunction, a, b), binary(Conjunction, not_a, not_b)),
+                Operator::MaterialCondition => (binary(Disjunction, not_a, b), binary(Conjunction, a, not_b)),
+                Operator::ExclusiveDisjunction | Operator::LogicalEquivalence => {
+                    let expand = match mode {
+                        XorMode::Expand => true,
+                        XorMode::Keep => false,
+                        XorMode::ExpandUnder(limit) => {
+                            // (A ∧ ¬B) ∨ (¬A ∧ B) against A ⊕ B
+                            let growth = 2 + node_count(&not_a) + node_count(&not_b);
+                            growth <= limit
+                        }
+                    };
+                    // A ⊕ B ≡ (A ∧ ¬B) ∨ (¬A ∧ B) and ¬(A ⊕ B) ≡ (¬A ∨ B) ∧ (A ∨ ¬B)
+                    let xor = |a: &Node, not_a: &Node, b: &Node, not_b: &Node| {
+                        if expand {
+                            (
+                                binary(Disjunction, binary(Conjunction, a.clone(), not_b.clone()), binary(Conjunction, not_a.clone(), b.clone())),
+                                binary(Conjunction, binary(Disjunction, not_a.clone(), b.clone()), binary(Disjunction, a.clone(), not_b.clone())),
+                            )
+                        } else {
+                            // ¬(A ⊕ B) ≡ A ↔ B
+                            (binary(ExclusiveDisjunction, a.clone(), b.clone()), binary(LogicalEquivalence, a.clone(), b.clone()))
+                        }
+                    };
+                    if *op == Operator::ExclusiveDisjunction {
+                        xor(&a, &not_a, &b, &not_b)
+                    } else if expand {
+                        // A ↔ B ≡ (¬A ∨ B) ∧ (¬B ∨ A) and ¬(A ↔ B) ≡ (A ∧ ¬B) ∨ (B ∧ ¬A)
+                        (
+                            binary(Conjunction, binary(Disjunction, not_a.clone(), b.clone()), binary(Disjunction, not_b.clone(), a.clone())),
+                            binary(Disjunction, binary(Conjunction, a, not_b), binary(Conjunction, b, not_a)),
+                        )
+                    } else {
+                        // ¬(A ↔ B) ≡ A ⊕ B
+                        let (pos, neg) = xor(&a, &not_a, &b, &not_b);
+                        (neg, pos)
+                    }
                 }
-                other => Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: Box::new(other),
-                },
+                Operator::Negation => panic!("Should not enter here"),
             }
         }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(double_negation(*lhs)),
-            rhs: Box::new(double_negation(*rhs)),
-        },
-        other => other
     }
 }
 
-fn do_all(node: Node) -> Node {
-    double_negation(de_morgans_law(material_conditon(equivalence(remove_xor(node)))))
-}
-
 fn ast_to_rpn(node: &Node) -> String {
     match node {
         Node::Value(val) => val.to_string(),
-        Node::Bool(_c) => panic!("Should not contain '1' or '0'"),
+        Node::Bool(val) => (*val as u8).to_string(),
         Node::UnaryExpr { op, child } => {
             let child_rpn = ast_to_rpn(child);
             format!("{}{}", child_rpn, operator_symbol(op))
@@ -303,15 +444,33 @@ fn operator_symbol(op: &Operator) -> &str {
 
 
 fn negation_normal_form(formula: &str) -> String{
-    let mut original = parse_formula(formula);
-    let mut root = do_all(parse_formula(formula));
-    while original != root {
-        original = root.clone();
-        root = do_all(root);
-    }
+    let (root, _) = derive_nnf(formula, Proof::disabled());
     ast_to_rpn(&root)
 }
 
+// Constants are folded before and after the conversion
+fn derive_nnf(formula: &str, mut proof: Proof) -> (Node, Proof) {
+    let root = simplify(parse_formula(formula), &mut proof);
+    let root = nnf(&root, true, &mut proof);
+    (simplify(root, &mut proof), proof)
+}
+
+// Numbered proof of every law negation_normal_form applies to the formula
+fn print_nnf_derivation(formula: &str) {
+    let (_, proof) = derive_nnf(formula, Proof::enabled());
+    proof.print(&parse_formula(formula));
+}
+
+// Same as negation_normal_form, but ^ and = can stay in the result (see XorMode)
+fn extended_negation_normal_form(formula: &str, mode: XorMode) -> String{
+    if mode == XorMode::Expand {
+        return negation_normal_form(formula);
+    }
+    let root = simplify(parse_formula(formula), &mut Proof::disabled());
+    let root = extended_nnf(&root, mode).0;
+    ast_to_rpn(&simplify(root, &mut Proof::disabled()))
+}
+
 
 
 fn main() {
@@ -328,6 +487,25 @@ fn main() {
     println!("{}", negation_normal_form("A!B!&!"));
     println!("{}", negation_normal_form("AB&C|DE&^FG|^HI&^"));
     println!("{}", negation_normal_form("AB&C|DE&!&!AB&C|DE&&|FG|!&!AB&C|DE&!&!AB&C|DE&&|FG|&|HI&!&!AB&C|DE&!&!AB&C|DE&&|FG|!&!AB&C|DE&!&!AB&C|DE&&|FG|&|HI&&|"));    
+
+    println!("{}", extended_negation_normal_form("AB^C^!", XorMode::Keep));
+    // AB^C=
+    println!("{}", extended_negation_normal_form("AB>C=!D^", XorMode::Keep));
+    // A!B|C^D^
+    println!("{}", extended_negation_normal_form("AB^C^D^", XorMode::ExpandUnder(6)));
+    // AB!&A!B&|C^D^
+
+    println!();
+    print_nnf_derivation("AB|C&!");
+
+    println!("{}", negation_normal_form("A1&B|"));
+    // AB|
+    println!("{}", negation_normal_form("A0>B^!"));
+    // AB|A!B!|&
+    println!("{}", negation_normal_form("A1^C|!"));
+    // AC!&
+    println!();
+    print_nnf_derivation("A1&B0=|");
 }
 
 
@@ -1,4 +1,5 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Operator {
@@ -77,6 +78,8 @@ fn parse_formula(formula: &str) -> Node{
             'A'..='Z' => {
                 tree.push(Value(c));
             }
+            '0' => tree.push(Bool(false)),
+            '1' => tree.push(Bool(true)),
             '!' =>{
                 let child = Box::new(tree.pop().expect("Missing value for !"));
                 tree.push(UnaryExpr {op: Negation, child });
@@ -113,7 +116,6 @@ fn parse_formula(formula: &str) -> Node{
     tree.pop().unwrap()
 }
 
-#[allow(dead_code)]
 fn parse_formula_char(formula: &str) ->  Vec<char> {
     let mut tree: Vec<Node> = Vec::new();
     let mut used_char: Vec<char> = Vec::new();
@@ -126,6 +128,9 @@ fn parse_formula_char(formula: &str) ->  Vec<char> {
                     used_char.push(c);
                 }
             }
+            '0' | '1' => {
+                continue;
+            }
             '!' =>{
                 continue;
             }
@@ -144,345 +149,2223 @@ fn parse_formula_char(formula: &str) ->  Vec<char> {
             '=' => {
                 continue;
             }
-            _ => panic!("Invalid char in the formula"),
+            _ => panic!("Invalid char in the formula"),
+        }
+    }
+    used_char
+}
+
+//=======================================================================================
+
+
+//=================================== DERIVATION LOG ====================================
+//=======================================================================================
+
+// One law applied to the subformula found at `path` (1 is the lhs or the only child, 2 the rhs)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub law: String,
+    pub path: Vec<u8>,
+    pub before: Node,
+    pub after: Node,
+}
+
+// Every rewrite gets one of these, when it is disabled nothing is cloned or stored
+#[derive(Debug, Default)]
+pub struct Proof {
+    enabled: bool,
+    path: Vec<u8>,
+    pub steps: Vec<Step>,
+}
+
+impl Proof {
+    fn disabled() -> Self {
+        Proof::default()
+    }
+
+    fn enabled() -> Self {
+        Proof { enabled: true, ..Default::default() }
+    }
+
+    // Run a rewrite on a child, keeping track of where in the tree it is
+    fn descend(&mut self, side: u8, node: Node, rewrite: fn(Node, &mut Proof) -> Node) -> Box<Node> {
+        self.path.push(side);
+        let node = rewrite(node, self);
+        self.path.pop();
+        Box::new(node)
+    }
+
+    fn snapshot(&self, before: impl FnOnce() -> Node) -> Option<Node> {
+        if self.enabled { Some(before()) } else { None }
+    }
+
+    fn record(&mut self, law: &str, before: Option<Node>, after: Node) -> Node {
+        if let Some(before) = before {
+            self.steps.push(Step { law: law.to_string(), path: self.path.clone(), before, after: after.clone() });
+        }
+        after
+    }
+
+    // Replays the steps from `start`, printing the whole formula after each one
+    fn print(&self, start: &Node) {
+        let mut current = start.clone();
+        println!("{:>4}. {}", 0, ast_to_rpn(&current));
+        for (i, step) in self.steps.iter().enumerate() {
+            let target = subtree_mut(&mut current, &step.path);
+            assert!(*target == step.before, "Step {} does not match the formula", i + 1);
+            *target = step.after.clone();
+            println!("{:>4}. {}", i + 1, ast_to_rpn(&current));
+            println!("      by {} at {}: {} ≡ {}", step.law, path_to_string(&step.path), ast_to_rpn(&step.before), ast_to_rpn(&step.after));
+        }
+    }
+}
+
+fn subtree_mut<'a>(node: &'a mut Node, path: &[u8]) -> &'a mut Node {
+    match (path.first(), node) {
+        (None, node) => node,
+        (Some(1), Node::UnaryExpr { op: _, child }) => subtree_mut(child, &path[1..]),
+        (Some(1), Node::BinaryExpr { op: _, lhs, rhs: _ }) => subtree_mut(lhs, &path[1..]),
+        (Some(2), Node::BinaryExpr { op: _, lhs: _, rhs }) => subtree_mut(rhs, &path[1..]),
+        _ => panic!("Invalid path in the derivation"),
+    }
+}
+
+fn path_to_string(path: &[u8]) -> String {
+    if path.is_empty() {
+        return "root".to_string();
+    }
+    path.iter().map(|side| side.to_string()).collect::<Vec<String>>().join(".")
+}
+
+//=======================================================================================
+
+
+//===================================== RULE ENGINE =====================================
+//=======================================================================================
+
+// `name: pattern => replacement` with both sides in RPN. Lowercase letters are
+// metavariables that stand for any subformula, uppercase letters are plain variables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    pub name: String,
+    pub lhs: Node,
+    pub rhs: Node,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Innermost, // one rewrite at a time, leftmost redex with no redex below it
+    Outermost, // one rewrite at a time, leftmost redex closest to the root
+    Fixpoint, // sweeps like the hard-coded passes, until a sweep changes nothing
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Normal, // no rule applies anymore
+    StepBound, // stopped after max_steps rewrites
+    Cycle, // came back to a formula it had already been through
+}
+
+fn is_meta(c: char) -> bool {
+    c.is_ascii_lowercase()
+}
+
+// Same as parse_formula, but with metavariables and errors instead of panics
+fn parse_pattern(rpn: &str) -> Result<Node, String> {
+    let mut tree: Vec<Node> = Vec::new();
+
+    for c in rpn.chars() {
+        match c {
+            'A'..='Z' | 'a'..='z' => tree.push(Value(c)),
+            '0' => tree.push(Bool(false)),
+            '1' => tree.push(Bool(true)),
+            '!' => {
+                let child = Box::new(tree.pop().ok_or("Missing value for !")?);
+                tree.push(UnaryExpr { op: Negation, child });
+            }
+            '&' | '|' | '^' | '>' | '=' => {
+                let rhs = Box::new(tree.pop().ok_or(format!("Missing rhs value for {c}"))?);
+                let lhs = Box::new(tree.pop().ok_or(format!("Missing lhs value for {c}"))?);
+                let op = match c {
+                    '&' => Conjunction,
+                    '|' => Disjunction,
+                    '^' => ExclusiveDisjunction,
+                    '>' => MaterialCondition,
+                    _ => LogicalEquivalence,
+                };
+                tree.push(BinaryExpr { op, lhs, rhs });
+            }
+            _ => return Err(format!("Invalid char '{c}' in the pattern")),
+        }
+    }
+    if tree.len() != 1 {
+        return Err("Invalid postfix expression".to_string());
+    }
+    Ok(tree.pop().unwrap())
+}
+
+fn metavariables(node: &Node, used: &mut Vec<char>) {
+    match node {
+        Node::Value(c) if is_meta(*c) && !used.contains(c) => used.push(*c),
+        Node::UnaryExpr { op: _, child } => metavariables(child, used),
+        Node::BinaryExpr { op: _, lhs, rhs } => {
+            metavariables(lhs, used);
+            metavariables(rhs, used);
+        }
+        _ => {}
+    }
+}
+
+// One rule per line, empty lines and lines starting with '#' are skipped.
+// Rules that could never terminate on their own are refused here
+fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    let mut rules = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let err = |msg: String| format!("line {}: {msg}", i + 1);
+        let (name, body) = line.split_once(':').ok_or_else(|| err("Missing ':' after the rule name".to_string()))?;
+        let (lhs, rhs) = body.split_once("=>").ok_or_else(|| err("Missing '=>' between the two sides".to_string()))?;
+        let lhs = parse_pattern(lhs.trim()).map_err(err)?;
+        let rhs = parse_pattern(rhs.trim()).map_err(err)?;
+        if matches!(lhs, Node::Value(c) if is_meta(c)) {
+            return Err(err("The pattern would match every subformula".to_string()));
+        }
+        let (mut lhs_metas, mut rhs_metas) = (Vec::new(), Vec::new());
+        metavariables(&lhs, &mut lhs_metas);
+        metavariables(&rhs, &mut rhs_metas);
+        if let Some(c) = rhs_metas.iter().find(|c| !lhs_metas.contains(c)) {
+            return Err(err(format!("'{c}' is not bound by the pattern")));
+        }
+        rules.push(Rule { name: name.trim().to_string(), lhs, rhs });
+    }
+    Ok(rules)
+}
+
+fn load_rules(path: &str) -> Result<Vec<Rule>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    parse_rules(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+// A metavariable seen twice has to match the same subformula both times
+fn match_pattern(pattern: &Node, node: &Node, bindings: &mut Vec<(char, Node)>) -> bool {
+    match (pattern, node) {
+        (Node::Value(c), _) if is_meta(*c) => match bindings.iter().find(|(meta, _)| meta == c) {
+            Some((_, bound)) => bound == node,
+            None => {
+                bindings.push((*c, node.clone()));
+                true
+            }
+        },
+        (Node::UnaryExpr { op, child }, Node::UnaryExpr { op: node_op, child: node_child }) => {
+            op == node_op && match_pattern(child, node_child, bindings)
+        }
+        (Node::BinaryExpr { op, lhs, rhs }, Node::BinaryExpr { op: node_op, lhs: node_lhs, rhs: node_rhs }) => {
+            op == node_op && match_pattern(lhs, node_lhs, bindings) && match_pattern(rhs, node_rhs, bindings)
+        }
+        _ => pattern == node,
+    }
+}
+
+fn instantiate(pattern: &Node, bindings: &[(char, Node)]) -> Node {
+    match pattern {
+        Node::Value(c) if is_meta(*c) => bindings.iter().find(|(meta, _)| meta == c).expect("Unbound metavariable").1.clone(),
+        Node::UnaryExpr { op, child } => Node::UnaryExpr { op: op.clone(), child: Box::new(instantiate(child, bindings)) },
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op: op.clone(),
+            lhs: Box::new(instantiate(lhs, bindings)),
+            rhs: Box::new(instantiate(rhs, bindings)),
+        },
+        other => other.clone(),
+    }
+}
+
+// The first rule, in file order, whose pattern matches the node itself
+fn apply_rules(node: &Node, rules: &[Rule], proof: &mut Proof) -> Option<Node> {
+    for rule in rules {
+        let mut bindings = Vec::new();
+        if match_pattern(&rule.lhs, node, &mut bindings) {
+            let before = proof.snapshot(|| node.clone());
+            return Some(proof.record(&rule.name, before, instantiate(&rule.rhs, &bindings)));
+        }
+    }
+    None
+}
+
+// A single rewrite somewhere in the tree, None when it is already in normal form
+fn rewrite_once(node: &Node, rules: &[Rule], strategy: Strategy, proof: &mut Proof) -> Option<Node> {
+    if strategy == Strategy::Outermost
+        && let Some(after) = apply_rules(node, rules, proof)
+    {
+        return Some(after);
+    }
+    let inner = match node {
+        Node::UnaryExpr { op, child } => {
+            proof.path.push(1);
+            let child = rewrite_once(child, rules, strategy, proof);
+            proof.path.pop();
+            child.map(|child| Node::UnaryExpr { op: op.clone(), child: Box::new(child) })
+        }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            proof.path.push(1);
+            let new_lhs = rewrite_once(lhs, rules, strategy, proof);
+            proof.path.pop();
+            match new_lhs {
+                Some(lhs) => Some(Node::BinaryExpr { op: op.clone(), lhs: Box::new(lhs), rhs: rhs.clone() }),
+                None => {
+                    proof.path.push(2);
+                    let new_rhs = rewrite_once(rhs, rules, strategy, proof);
+                    proof.path.pop();
+                    new_rhs.map(|rhs| Node::BinaryExpr { op: op.clone(), lhs: lhs.clone(), rhs: Box::new(rhs) })
+                }
+            }
+        }
+        _ => None,
+    };
+    if inner.is_none() && strategy == Strategy::Innermost {
+        return apply_rules(node, rules, proof);
+    }
+    inner
+}
+
+// Rewrites every redex met going down, without looking inside what a rule just produced,
+// the way de_morgans_law and double_negation walk the tree
+fn sweep(node: Node, rules: &[Rule], proof: &mut Proof, steps: &mut usize, max_steps: usize) -> Node {
+    if *steps >= max_steps {
+        return node;
+    }
+    if let Some(after) = apply_rules(&node, rules, proof) {
+        *steps += 1;
+        return after;
+    }
+    match node {
+        Node::UnaryExpr { op, child } => {
+            proof.path.push(1);
+            let child = sweep(*child, rules, proof, steps, max_steps);
+            proof.path.pop();
+            Node::UnaryExpr { op, child: Box::new(child) }
+        }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            proof.path.push(1);
+            let lhs = sweep(*lhs, rules, proof, steps, max_steps);
+            proof.path.pop();
+            proof.path.push(2);
+            let rhs = sweep(*rhs, rules, proof, steps, max_steps);
+            proof.path.pop();
+            Node::BinaryExpr { op, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+        }
+        other => other,
+    }
+}
+
+// Rewrite until no rule applies, at most max_steps rule applications.
+// Every formula met is remembered so a set of rules going round in circles is caught
+fn rewrite(node: Node, rules: &[Rule], strategy: Strategy, max_steps: usize, proof: &mut Proof) -> (Node, Termination) {
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut steps = 0usize;
+    let mut node = node;
+    seen.insert(ast_to_rpn(&node));
+    loop {
+        if steps >= max_steps {
+            return (node, Termination::StepBound);
+        }
+        let next = if strategy == Strategy::Fixpoint {
+            let before = steps;
+            let after = sweep(node.clone(), rules, proof, &mut steps, max_steps);
+            if steps == before { None } else { Some(after) }
+        } else {
+            let after = rewrite_once(&node, rules, strategy, proof);
+            if after.is_some() {
+                steps += 1;
+            }
+            after
+        };
+        match next {
+            None => return (node, Termination::Normal),
+            Some(after) => {
+                node = after;
+                if !seen.insert(ast_to_rpn(&node)) {
+                    return (node, Termination::Cycle);
+                }
+            }
+        }
+    }
+}
+
+fn rules_path(file: &str) -> String {
+    format!("{}/rules/{file}", env!("CARGO_MANIFEST_DIR"))
+}
+
+fn equivalence(node: Node, proof: &mut Proof) -> Node {
+    match node {
+        Node::BinaryExpr { op: Operator::LogicalEquivalence, lhs, rhs } => {
+            // Recursively expand children first
+            let lhs = proof.descend(1, *lhs, equivalence);
+            let rhs = proof.descend(2, *rhs, equivalence);
+            let before = proof.snapshot(|| Node::BinaryExpr { op: LogicalEquivalence, lhs: lhs.clone(), rhs: rhs.clone() });
+
+            // Contruct the Material condition of lhs with rhs
+            let left_and = Node::BinaryExpr {
+                op: Operator::MaterialCondition,
+                lhs: lhs.clone(),
+                rhs: rhs.clone(),
+            };
+
+            // Contruct the Material condition of rhs with lhs
+            let right_and = Node::BinaryExpr {
+                op: Operator::MaterialCondition,
+                lhs: rhs.clone(),
+                rhs: lhs.clone(),
+            };
+
+            // Return the Conjunction of the left and right conditions
+            proof.record("Equivalence", before, Node::BinaryExpr {
+                op: Operator::Conjunction,
+                lhs: Box::new(left_and),
+                rhs: Box::new(right_and),
+            })
+        }
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, equivalence),
+            rhs: proof.descend(2, *rhs, equivalence),
+        },
+        Node::UnaryExpr { op, child } => Node::UnaryExpr {
+            op,
+            child: proof.descend(1, *child, equivalence),
+        },
+        other => other,
+    }
+}
+
+
+
+fn material_conditon(node: Node, proof: &mut Proof) -> Node {
+    match node {
+        Node::BinaryExpr { op: Operator::MaterialCondition, lhs, rhs } => {
+            // Recursively expand children first
+            let lhs = proof.descend(1, *lhs, material_conditon);
+            let rhs = proof.descend(2, *rhs, material_conditon);
+            let before = proof.snapshot(|| Node::BinaryExpr { op: MaterialCondition, lhs: lhs.clone(), rhs: rhs.clone() });
+
+            // Construct (¬lhs ∧ rhs)
+            proof.record("Material condition", before, Node::BinaryExpr {
+                op: Operator::Disjunction,
+                lhs: Box::new(Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: lhs,
+                }),
+                rhs,
+            })
+        }
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, material_conditon),
+            rhs: proof.descend(2, *rhs, material_conditon),
+        },
+        Node::UnaryExpr { op, child } => Node::UnaryExpr {
+            op,
+            child: proof.descend(1, *child, material_conditon),
+        },
+        other => other,
+    }
+}
+
+
+fn remove_xor(node: Node, proof: &mut Proof) -> Node {
+    match node {
+        Node::BinaryExpr { op: Operator::ExclusiveDisjunction, lhs, rhs } => {
+            // Recursively expand children first
+            let lhs = proof.descend(1, *lhs, remove_xor);
+            let rhs = proof.descend(2, *rhs, remove_xor);
+            let before = proof.snapshot(|| Node::BinaryExpr { op: ExclusiveDisjunction, lhs: lhs.clone(), rhs: rhs.clone() });
+
+            // Construct (lhs ∧ ¬rhs)
+            let left_and = Node::BinaryExpr {
+                op: Operator::Conjunction,
+                lhs: lhs.clone(),
+                rhs: Box::new(Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: rhs.clone(),
+                }),
+            };
+
+            // Construct (¬lhs ∧ rhs)
+            let right_and = Node::BinaryExpr {
+                op: Operator::Conjunction,
+                lhs: Box::new(Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: lhs,
+                }),
+                rhs,
+            };
+
+            // Return (lhs ∧ ¬rhs) ∨ (¬lhs ∧ rhs)
+            proof.record("XOR elimination", before, Node::BinaryExpr {
+                op: Operator::Disjunction,
+                lhs: Box::new(left_and),
+                rhs: Box::new(right_and),
+            })
+        }
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, remove_xor),
+            rhs: proof.descend(2, *rhs, remove_xor),
+        },
+        Node::UnaryExpr { op, child } => Node::UnaryExpr {
+            op,
+            child: proof.descend(1, *child, remove_xor),
+        },
+        other => other,
+    }
+}
+
+
+fn de_morgans_law(node: Node, proof: &mut Proof) -> Node{
+    match node {
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, de_morgans_law),
+            rhs: proof.descend(2, *rhs, de_morgans_law),
+        },
+        Node::UnaryExpr { op: Negation, child } =>{
+            let before = proof.snapshot(|| Node::UnaryExpr { op: Negation, child: child.clone() });
+            match *child{
+                Node::BinaryExpr {op: Operator::Conjunction, lhs, rhs} => {
+                    proof.record("De Morgan", before, Node::BinaryExpr {
+                        op: Operator::Disjunction,
+                        lhs: Box::new(Node::UnaryExpr {
+                            op: Operator::Negation,
+                            child: Box::new(*lhs),
+                        }),
+                        rhs: Box::new(Node::UnaryExpr {
+                            op: Operator::Negation,
+                            child: Box::new(*rhs),
+                        }),
+                    })
+                }
+                Node::BinaryExpr {op: Operator::Disjunction, lhs, rhs} => {
+                    proof.record("De Morgan", before, Node::BinaryExpr {
+                        op: Operator::Conjunction,
+                        lhs: Box::new(Node::UnaryExpr {
+                            op: Operator::Negation,
+                            child: Box::new(*lhs),
+                        }),
+                        rhs: Box::new(Node::UnaryExpr {
+                            op: Operator::Negation,
+                            child: Box::new(*rhs),
+                        }),
+                    })
+                }
+                other => Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: Box::new(other),
+                }
+            }
+        }
+        other => other,
+    }
+}
+
+
+
+// last step removing the double negations
+fn double_negation(node: Node, proof: &mut Proof) -> Node {
+     match node {
+        Node::UnaryExpr { op: Operator::Negation, child} => {
+            match *child {
+                Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: inner,
+                } => {
+                    let before = proof.snapshot(|| Node::UnaryExpr {
+                        op: Negation,
+                        child: Box::new(Node::UnaryExpr { op: Negation, child: inner.clone() }),
+                    });
+                    proof.record("Double negation", before, *inner) // Double negation: remove both
+                }
+                other => Node::UnaryExpr {
+                    op: Operator::Negation,
+                    child: Box::new(other),
+                },
+            }
+        }
+        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, double_negation),
+            rhs: proof.descend(2, *rhs, double_negation),
+        },
+        other => other
+    }
+}
+
+// Expects NNF, so the only operators left are ∧, ∨ and negated letters. Every ∧ and ∨
+// is reduced on the way up, so each distribution multiplies clause lists that are
+// already free of duplicates, tautologies and absorbed clauses
+fn distributivity(node: Node, proof: &mut Proof) -> Node{
+    match distribute_over(node, Disjunction, proof) {
+        node @ Node::BinaryExpr { .. } => {
+            let node = simplify(node, proof);
+            reduce_clauses(node, proof)
+        }
+        other => other,
+    }
+}
+
+// The dual, ∧ goes under ∨ for the DNF, reduced the same way with cubes
+fn dual_distributivity(node: Node, proof: &mut Proof) -> Node{
+    match distribute_over(node, Conjunction, proof) {
+        node @ Node::BinaryExpr { .. } => {
+            let node = simplify(node, proof);
+            reduce_cubes(node, proof)
+        }
+        other => other,
+    }
+}
+
+fn distribute_over(node: Node, outer: Operator, proof: &mut Proof) -> Node {
+    let pass: fn(Node, &mut Proof) -> Node = if outer == Disjunction { distributivity } else { dual_distributivity };
+    match node {
+        Node::BinaryExpr {op, lhs, rhs} if op == outer => {
+            // Recursively expand children first, both sides are then in normal form
+            let lhs = proof.descend(1, *lhs, pass);
+            let rhs = proof.descend(2, *rhs, pass);
+            distribute(outer, *lhs, *rhs, proof)
+        }
+        Node::UnaryExpr { op, child } => Node::UnaryExpr {
+            op,
+            child: proof.descend(1, *child, pass),
+        },
+        Node::BinaryExpr {op, lhs, rhs} => Node::BinaryExpr {
+            op,
+            lhs: proof.descend(1, *lhs, pass),
+            rhs: proof.descend(2, *rhs, pass),
+        },
+        other => other,
+    }
+}
+
+// Normal form of lhs `outer` rhs when lhs and rhs already are in normal form,
+// `outer` is ∨ for the CNF and ∧ for the DNF
+fn distribute(outer: Operator, lhs: Node, rhs: Node, proof: &mut Proof) -> Node {
+    let inner = if outer == Disjunction { Conjunction } else { Disjunction };
+    let before = proof.snapshot(|| Node::BinaryExpr { op: outer.clone(), lhs: Box::new(lhs.clone()), rhs: Box::new(rhs.clone()) });
+    let (x, y, z, z_first) = match (lhs, rhs) {
+        // (X ∧ Y) ∨ Z ≡ (X ∨ Z) ∧ (Y ∨ Z)
+        (Node::BinaryExpr { op, lhs: x, rhs: y }, z) if op == inner => (*x, *y, z, false),
+        // Z ∨ (X ∧ Y) ≡ (Z ∨ X) ∧ (Z ∨ Y)
+        (z, Node::BinaryExpr { op, lhs: x, rhs: y }) if op == inner => (*x, *y, z, true),
+        (lhs, rhs) => return Node::BinaryExpr { op: outer, lhs: Box::new(lhs), rhs: Box::new(rhs) },
+    };
+    let pair = |a: Node, b: Node| if z_first { (b, a) } else { (a, b) };
+    let (left, right) = (pair(x, z.clone()), pair(y, z));
+    proof.record("Distributivity", before, Node::BinaryExpr {
+        op: inner.clone(),
+        lhs: Box::new(Node::BinaryExpr { op: outer.clone(), lhs: Box::new(left.0.clone()), rhs: Box::new(left.1.clone()) }),
+        rhs: Box::new(Node::BinaryExpr { op: outer.clone(), lhs: Box::new(right.0.clone()), rhs: Box::new(right.1.clone()) }),
+    });
+
+    // X and Z can still hold the inner operator, keep distributing inside the new terms
+    proof.path.push(1);
+    let lhs = distribute(outer.clone(), left.0, left.1, proof);
+    proof.path.pop();
+    proof.path.push(2);
+    let rhs = distribute(outer, right.0, right.1, proof);
+    proof.path.pop();
+    Node::BinaryExpr { op: inner, lhs: Box::new(lhs), rhs: Box::new(rhs) }
+}
+
+// Terms of a chain of `op`, (A ∨ B) ∨ C gives [A, B, C]
+fn flatten(node: Node, op: &Operator, terms: &mut Vec<Node>) {
+    match node {
+        Node::BinaryExpr { op: node_op, lhs, rhs } if node_op == *op => {
+            flatten(*lhs, op, terms);
+            flatten(*rhs, op, terms);
+        }
+        other => terms.push(other),
+    }
+}
+
+// A ∨ (B ∨ C) from [A, B, C], the nesting conjunctive_normal_form gives too
+fn nest(op: &Operator, terms: Vec<Node>) -> Node {
+    terms
+        .into_iter()
+        .rev()
+        .reduce(|rest, term| Node::BinaryExpr { op: op.clone(), lhs: Box::new(term), rhs: Box::new(rest) })
+        .expect("Nothing to nest")
+}
+
+// An empty ∨ of cubes is 0, an empty ∧ of clauses is 1
+fn terms_to_node(outer: &Operator, terms: &[Vec<Node>]) -> Node {
+    if terms.is_empty() {
+        return Bool(*outer == Conjunction);
+    }
+    let inner = if *outer == Disjunction { Conjunction } else { Disjunction };
+    nest(outer, terms.iter().map(|term| nest(&inner, term.clone())).collect())
+}
+
+// Tidies a DNF: drops the cubes holding a letter and its negation, the literals and
+// cubes written twice, and the cubes another one absorbs (A ∨ A ∧ B ≡ A)
+fn reduce_cubes(node: Node, proof: &mut Proof) -> Node {
+    reduce_terms(node, Disjunction, "Contradiction", proof)
+}
+
+// The dual for a CNF: drops the clauses holding a letter and its negation (A ∨ ¬A ≡ 1),
+// the literals and clauses written twice, and the clauses another one absorbs
+// (A ∧ (A ∨ B) ≡ A)
+fn reduce_clauses(node: Node, proof: &mut Proof) -> Node {
+    reduce_terms(node, Conjunction, "Complement", proof)
+}
+
+fn reduce_terms(node: Node, outer: Operator, complement_law: &str, proof: &mut Proof) -> Node {
+    if let Bool(_) = node {
+        return node;
+    }
+    let inner = if outer == Disjunction { Conjunction } else { Disjunction };
+    let mut flat = Vec::new();
+    flatten(node.clone(), &outer, &mut flat);
+    let mut terms: Vec<Vec<Node>> = flat
+        .into_iter()
+        .map(|term| {
+            let mut literals = Vec::new();
+            flatten(term, &inner, &mut literals);
+            literals
+        })
+        .collect();
+    let mut current = node;
+    let mut step = |law: &str, terms: &[Vec<Node>], proof: &mut Proof| {
+        let after = terms_to_node(&outer, terms);
+        if after != current {
+            let before = proof.snapshot(|| current.clone());
+            current = proof.record(law, before, after);
+        }
+    };
+    step("Associativity", &terms, proof);
+
+    let negate = |literal: &Node| match literal {
+        Node::UnaryExpr { op: _, child } => (**child).clone(),
+        other => Node::UnaryExpr { op: Negation, child: Box::new(other.clone()) },
+    };
+    terms.retain(|term| !term.iter().any(|literal| term.contains(&negate(literal))));
+    step(complement_law, &terms, proof);
+
+    for term in terms.iter_mut() {
+        let mut seen = Vec::new();
+        term.retain(|literal| {
+            let first = !seen.contains(literal);
+            seen.push(literal.clone());
+            first
+        });
+    }
+    let mut seen: Vec<Vec<Node>> = Vec::new();
+    terms.retain(|term| {
+        let first = !seen.iter().any(|other| other.len() == term.len() && other.iter().all(|literal| term.contains(literal)));
+        seen.push(term.clone());
+        first
+    });
+    step("Idempotence", &terms, proof);
+
+    let all = terms.clone();
+    terms.retain(|term| {
+        !all.iter().any(|other| other.len() < term.len() && other.iter().all(|literal| term.contains(literal)))
+    });
+    step("Absorption", &terms, proof);
+    current
+}
+
+// Fold the constants away: A∧1 → A, A∨1 → 1, A⊕0 → A, 1→A → A...
+// Bottom-up, so afterwards either the whole formula is 0 or 1 or no constant is left
+fn simplify(node: Node, proof: &mut Proof) -> Node {
+    match node {
+        Node::UnaryExpr { op, child } => {
+            let child = proof.descend(1, *child, simplify);
+            match *child {
+                Node::Bool(val) => {
+                    let before = proof.snapshot(|| Node::UnaryExpr { op: Negation, child: Box::new(Bool(val)) });
+                    proof.record("Constant evaluation", before, Bool(!val))
+                }
+                child => Node::UnaryExpr { op, child: Box::new(child) },
+            }
+        }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let lhs = proof.descend(1, *lhs, simplify);
+            let rhs = proof.descend(2, *rhs, simplify);
+            if !matches!(*lhs, Bool(_)) && !matches!(*rhs, Bool(_)) {
+                return Node::BinaryExpr { op, lhs, rhs };
+            }
+            let before = proof.snapshot(|| Node::BinaryExpr { op: op.clone(), lhs: lhs.clone(), rhs: rhs.clone() });
+            let negate = |node: Node| Node::UnaryExpr { op: Negation, child: Box::new(node) };
+            let (law, after) = match (op, *lhs, *rhs) {
+                (op, Bool(a), Bool(b)) => ("Constant evaluation", Bool(match op {
+                    Conjunction => a & b,
+                    Disjunction => a | b,
+                    ExclusiveDisjunction => a ^ b,
+                    MaterialCondition => !a | b,
+                    LogicalEquivalence => a == b,
+                    Negation => panic!("Should not enter here"),
+                })),
+                (Conjunction, Bool(true), x) | (Conjunction, x, Bool(true)) => ("Identity", x),
+                (Disjunction, Bool(false), x) | (Disjunction, x, Bool(false)) => ("Identity", x),
+                (ExclusiveDisjunction, Bool(false), x) | (ExclusiveDisjunction, x, Bool(false)) => ("Identity", x),
+                (LogicalEquivalence, Bool(true), x) | (LogicalEquivalence, x, Bool(true)) => ("Identity", x),
+                (MaterialCondition, Bool(true), x) => ("Identity", x),
+                (Conjunction, _, _) => ("Annihilation", Bool(false)),
+                (Disjunction, _, _) => ("Annihilation", Bool(true)),
+                (MaterialCondition, Bool(false), _) | (MaterialCondition, _, Bool(true)) => ("Annihilation", Bool(true)),
+                (MaterialCondition, x, _) => ("Complement", negate(x)),
+                (ExclusiveDisjunction, Bool(true), x) | (ExclusiveDisjunction, x, _) => ("Complement", negate(x)),
+                (LogicalEquivalence, Bool(false), x) | (LogicalEquivalence, x, _) => ("Complement", negate(x)),
+                (Negation, _, _) => panic!("Should not enter here"),
+            };
+            proof.record(law, before, after)
+        }
+        other => other,
+    }
+}
+
+fn do_all(node: Node, proof: &mut Proof) -> Node {
+    let node = remove_xor(node, proof);
+    let node = equivalence(node, proof);
+    let node = material_conditon(node, proof);
+    let node = de_morgans_law(node, proof);
+    double_negation(node, proof)
+}
+
+
+fn conjunctive_normal_form(formula: &str) -> String{
+    // let mut used_char = parse_formula_char(formula);
+    // if used_char.len() >= 2 && used_char.len() <= 4 {
+    //     if used_char.len() == 4 {
+    //         println!("K-MAP: {}", karnaugh_map4(formula, &mut used_char));
+    //     }
+    //     if used_char.len() == 3 {
+    //         println!("K-MAP: {}", karnaugh_map3(formula, &mut used_char));
+    //     }
+    //     if used_char.len() == 2 {
+    //         println!("K-MAP: {}", karnaugh_map2(formula, &mut used_char));
+    //     }
+    //     
+    // }
+    let (root, _) = derive_cnf(formula, Proof::disabled());
+    ast_to_rpn(&root)
+}
+
+// Constants are folded before and after the conversion
+fn derive_cnf(formula: &str, mut proof: Proof) -> (Node, Proof) {
+    let root = derive_nnf(formula, &mut proof);
+    // Distribute only once the negations sit on the letters, one pass is enough then
+    let root = distributivity(root, &mut proof);
+    let root = simplify(root, &mut proof);
+    (reduce_clauses(root, &mut proof), proof)
+}
+
+fn derive_nnf(formula: &str, proof: &mut Proof) -> Node {
+    let mut original = simplify(parse_formula(formula), proof);
+    let mut root = do_all(original.clone(), proof);
+    while original != root {
+        original = root.clone();
+        root = do_all(root, proof);
+    }
+    root
+}
+
+// Same RPN style as conjunctive_normal_form, an ∨ of cubes (∧ of literals)
+fn disjunctive_normal_form(formula: &str) -> String {
+    let (root, _) = derive_dnf(formula, Proof::disabled());
+    ast_to_rpn(&root)
+}
+
+fn derive_dnf(formula: &str, mut proof: Proof) -> (Node, Proof) {
+    let root = derive_nnf(formula, &mut proof);
+    let root = dual_distributivity(root, &mut proof);
+    let root = simplify(root, &mut proof);
+    (reduce_cubes(root, &mut proof), proof)
+}
+
+// Same truth table over the letters of both formulas
+fn equivalent(f: &str, g: &str) -> bool {
+    let mut used_char = parse_formula_char(f);
+    for c in parse_formula_char(g) {
+        if !used_char.contains(&c) {
+            used_char.push(c);
+        }
+    }
+    (0..1i64 << used_char.len()).all(|i| {
+        evaluate(&give_value_to_char(i, f, &used_char)) == evaluate(&give_value_to_char(i, g, &used_char))
+    })
+}
+
+// Only ∧ above ∨, and negations only on letters
+fn is_cnf(node: &Node) -> bool {
+    fn is_clause(node: &Node) -> bool {
+        match node {
+            Node::BinaryExpr { op: Disjunction, lhs, rhs } => is_clause(lhs) && is_clause(rhs),
+            Node::UnaryExpr { op: _, child } => matches!(**child, Value(_)),
+            Node::Value(_) | Node::Bool(_) => true,
+            _ => false,
+        }
+    }
+    match node {
+        Node::BinaryExpr { op: Conjunction, lhs, rhs } => is_cnf(lhs) && is_cnf(rhs),
+        other => is_clause(other),
+    }
+}
+
+// Only ∨ above ∧, and negations only on letters
+fn is_dnf(node: &Node) -> bool {
+    fn is_cube(node: &Node) -> bool {
+        match node {
+            Node::BinaryExpr { op: Conjunction, lhs, rhs } => is_cube(lhs) && is_cube(rhs),
+            Node::UnaryExpr { op: _, child } => matches!(**child, Value(_)),
+            Node::Value(_) | Node::Bool(_) => true,
+            _ => false,
+        }
+    }
+    match node {
+        Node::BinaryExpr { op: Disjunction, lhs, rhs } => is_dnf(lhs) && is_dnf(rhs),
+        other => is_cube(other),
+    }
+}
+
+// Numbered proof of every law conjunctive_normal_form applies to the formula
+fn print_cnf_derivation(formula: &str) {
+    let (_, proof) = derive_cnf(formula, Proof::enabled());
+    proof.print(&parse_formula(formula));
+}
+
+
+
+fn main() {
+    println!("{}", conjunctive_normal_form("AB&!"));
+    // A!B!|
+    println!("{}", conjunctive_normal_form("AB|!"));
+    // A!B!&
+    println!("{}", conjunctive_normal_form("AB|C&"));
+    // AB|C&
+    println!("{}", conjunctive_normal_form("AB|C|D|"));
+    // ABCD|||
+    println!("{}", conjunctive_normal_form("AB&!C!|"));
+    // A!B!C!||
+    println!("{}", conjunctive_normal_form("AB|!C!&"));
+    // A!B!C!&&
+    println!("{}", conjunctive_normal_form("AB&C&D&"));
+    println!("{}", conjunctive_normal_form("A!B&C&D&B&"));
+    println!("{}", conjunctive_normal_form("BCD!A!&&&"));
+    println!("{}", conjunctive_normal_form("AB|C!D|&"));
+    println!("{}", conjunctive_normal_form("CD^A!A|&B!B|&"));
+    println!("{}", conjunctive_normal_form("AC|BC|AD|BD|&&&"));
+    println!("{}", conjunctive_normal_form("AB|D|BC|D!|AC!|D|BC!|D|&&&"));
+    println!("{}", conjunctive_normal_form("ABCD|||"));
+    println!("{}", conjunctive_normal_form("B!C!|A!C|D!|ABCD|||&&"));
+    println!();
+    print_cnf_derivation("AB=C!>");
+    println!();
+    println!("{}", conjunctive_normal_form("A1&B|"));
+    // AB|
+    println!("{}", conjunctive_normal_form("A0|B1>&"));
+    // A
+    println!("{}", conjunctive_normal_form("A1|B&0^"));
+    // B
+
+    println!();
+    let mut rules = load_rules(&rules_path("de_morgans_law.rules")).expect("Could not load the rules");
+    rules.extend(load_rules(&rules_path("double_negation.rules")).expect("Could not load the rules"));
+    let (root, termination) = rewrite(parse_formula("AB&!C|!"), &rules, Strategy::Fixpoint, 100, &mut Proof::disabled());
+    println!("{} {:?}", ast_to_rpn(&root), termination);
+    // AB&C!& Normal
+    let (root, _) = rewrite(parse_formula("AB&!C|!"), &rules, Strategy::Innermost, 100, &mut Proof::disabled());
+    println!("{}", ast_to_rpn(&root));
+    // AB&C!&
+    let swap = parse_rules("swap: xy| => yx|").expect("Could not parse the rules");
+    let (root, termination) = rewrite(parse_formula("AB|"), &swap, Strategy::Outermost, 100, &mut Proof::disabled());
+    println!("{} {:?}", ast_to_rpn(&root), termination);
+    // AB| Cycle
+
+    println!();
+    println!("{}", conjunctive_normal_form("AB&C|"));
+    // AC|BC|&
+    println!("{}", conjunctive_normal_form("AB&CD&|"));
+    // AC|AD|BC|BD|&&&
+    for formula in ["AB&!", "AB|C&", "AB&C|", "AB&CD&|", "AB=C!>", "CD^A!A|&B!B|&", "AB&C|DE&^", "A!B&C>D=E^!"] {
+        let cnf = conjunctive_normal_form(formula);
+        assert!(is_cnf(&parse_formula(&cnf)), "{formula} gave {cnf}, not in CNF");
+        assert!(equivalent(formula, &cnf), "{formula} and {cnf} do not have the same truth table");
+    }
+    let cnf = conjunctive_normal_form("BB|C^BC=&CC>A^AB=AB^=^^");
+    assert!(is_cnf(&parse_formula(&cnf)), "{cnf} is not in CNF");
+    assert!(cnf.len() <= 32, "{cnf} grew while distributing");
+    println!("{cnf}");
+    // BCA!||C!B!A!||B!CA!||C!BA!||&&&
+
+    println!();
+    let root = parse_formula("AB&C|DE&^FG|^HI&^");
+    println!("{} clauses by distribution", conjunctive_normal_form("AB&C|DE&^FG|^HI&^").matches('&').count() + 1);
+    // 54 clauses by distribution
+    println!("{} clauses with Tseitin", tseitin_cnf(&root).clauses.len());
+    // 28 clauses with Tseitin
+    println!("{} clauses with Plaisted-Greenbaum", plaisted_greenbaum_cnf(&root).clauses.len());
+    // 26 clauses with Plaisted-Greenbaum
+    print_encoded_cnf(&plaisted_greenbaum_cnf(&parse_formula("AB&C>")));
+    // (¬A ∨ ¬B ∨ x27) ∧ (¬x28 ∨ ¬x27 ∨ C) ∧ (x28)
+    //   x27 ≡ AB&
+    //   x28 ≡ AB&C>
+
+    println!();
+    println!("{}", disjunctive_normal_form("AB|C&"));
+    // AC&BC&|
+    println!("{}", disjunctive_normal_form("AB|!C>"));
+    // ABC||
+    println!("{}", disjunctive_normal_form("AB>BA>&"));
+    // A!B!&BA&|
+    println!("{}", disjunctive_normal_form("AB&A|AA!&|"));
+    // A
+    for formula in ["AB|C&", "AB|CD|&", "AB=C!>", "AB^C&", "AB&C|DE&^", "A!B&C>D=E^!", "AA!&", "A1&"] {
+        let dnf = disjunctive_normal_form(formula);
+        assert!(is_dnf(&parse_formula(&dnf)), "{formula} gave {dnf}, not in DNF");
+        assert!(equivalent(formula, &dnf), "{formula} and {dnf} do not have the same truth table");
+    }
+
+    println!();
+    println!("{}", minimize_sop("AB&AB!&|"));
+    // A
+    println!("{}", minimize_sop("AB|AC|&"));
+    // ABC&|
+    println!("{}", minimize_pos("AB|AC|&"));
+    // AB|AC|&
+    println!("{}", minimize_sop("AB=C|"));
+    // AB&A!B!&C||
+    println!("{}", minimize_pos("AB=C|"));
+    // A!BC||AB!C||&
+    println!("{}", minimize_sop("AB^C^D^"));
+    // ABCD!&&&ABC!D&&&AB!CD&&&AB!C!D!&&&A!BCD&&&A!BC!D!&&&A!B!CD!&&&A!B!C!D&&&|||||||
+    println!("{}", minimize_sop("GD!&HD=|FF|B!C&||BD!&GB!&=EA|BA^^||"));
+    // G!H!&GEA!&&DH&D!B!&H!C&FBE!&BA&|||||||
+    let on: Vec<u32> = (0..256u32).filter(|row| row.wrapping_mul(2_654_435_761) >> 29 < 4).collect();
+    let cover = minimize(8, &on, &[]);
+    println!("{} lines, {} products, {} literals", on.len(), cover.len(), cover.iter().map(|implicant| implicant.literals(8)).sum::<usize>());
+    // 128 lines, 41 products, 237 literals
+    println!("{}", minimize_sop("AA!&"));
+    // 0
+    println!("{}", minimize_pos("AA!|"));
+    // 1
+    println!("{}", minimize_sop("A!B!&C!&A!B&C&|AB!&C&|AB&C!&|AB&C&|A!B!&C&|"));
+    // AB&A!B!&C||
+
+    println!();
+    let (sop, report) = espresso_formula("A!B!&C!&A!B&C&|AB!&C&|AB&C!&|AB&C&|A!B!&C&|", EspressoMode::Deterministic);
+    println!("{sop}");
+    // AB&A!B!&C||
+    print_cover_report(&report);
+    // 3 products and 5 literals, against 6 products and 18 literals in canonical form
+    let decoder = "ABCDE&&&&ABCDE!&&&&|FG&H&I&J!&|KL&M&N&O&|A!KPQRST&&&&&&|";
+    let (sop, report) = espresso_formula(decoder, EspressoMode::Deterministic);
+    println!("{sop}");
+    // ABCD&&&A!KPQRST&&&&&&FGHIJ!&&&&KLMNO&&&&|||
+    print_cover_report(&report);
+    // 4 products and 21 literals, against 133456 products and 2669120 literals in canonical form
+    let (_, report) = espresso_formula(decoder, EspressoMode::Randomized { restarts: 4, seed: 42 });
+    print_cover_report(&report);
+    // 4 products and 21 literals, against 133456 products and 2669120 literals in canonical form
+    let table: Vec<bool> = (0..16).map(|row| row % 3 == 0).collect();
+    let (sop, report) = espresso_table(&['A', 'B', 'C', 'D'], &table, EspressoMode::Deterministic);
+    println!("{sop}");
+    // ABCD&&&ABC!D!&&&AB!C!D&&&A!BCD!&&&A!B!CD&&&A!B!C!D!&&&|||||
+    print_cover_report(&report);
+    // 6 products and 24 literals, against 6 products and 24 literals in canonical form
+
+    println!();
+    print_karnaugh_map("B!D!&A!A|&C!C|&");
+    // a = B!D!& holds the four corners
+    println!();
+    print_karnaugh_map("AB&C|");
+    // a = AB& and b = C share the cell A = 1, BC = 11
+    println!();
+    print_karnaugh_map("AC!&E!&AB&D&|");
+    // b = AC!E!&& wraps around both the rows and the columns of the plane A = 1
+    println!();
+    print_karnaugh_map("AB=CF&|E!D&|");
+    // c = CF& and d = DE!& show up in all four planes, a = AB& and b = A!B!& fill one each
+
+    println!();
+    let function = IncompleteFunction::from_minterms(&['A', 'B', 'C', 'D'], &[1, 3, 7, 11, 15], &[0, 2, 5]);
+    println!("{}", function.minimize_sop());
+    // A!B!&CD&|
+    println!("{}", function.minimize_pos());
+    // A!C|D&
+    print!("{}", function.karnaugh_map());
+    // a = A!B!& takes the don't cares 0000 and 0010, 0101 stays out of every group
+    let function = IncompleteFunction::from_formulas("AB&", "AB!&");
+    println!("{}", function.minimize_sop());
+    // A
+    println!("{}", IncompleteFunction::from_formulas("AB&C&", "A!").minimize_pos());
+    // BC&
+
+    println!();
+    println!("{}", blake_canonical_form("AB&A!C&|"));
+    // AB&A!C&BC&||
+    println!("{}", prime_implicates("AB&A!C&|"));
+    // A!B|AC|BC|&&
+    print_prime_implicants("AB&A!C&|");
+    // AB& essential
+    // A!C& essential
+    // BC&
+    println!("{}", blake_canonical_form("A!B&B!C&|C!A&|"));
+    // AB!&A!B&AC!&A!C&BC!&B!C&|||||
+    println!("{}", blake_canonical_form("AA!&"));
+    // 0
+    println!("{}", prime_implicates("AA!|"));
+    // 1
+
+    println!();
+    print_anf("AB&C|DE&^FG|^HI&^");
+    // CFGAB&DE&FG&HI&ABC&&^^^^^^^ of degree 3
+    print_anf("AB|");
+    // ABAB&^^ of degree 2
+    print_anf("AB>");
+    // 1AAB&^^ of degree 2
+    print_anf("AB=C=");
+    // ABC^^ of degree 1
+    print_anf("AA^");
+    // 0 of degree 0
+    print_anf("AA!|");
+    // 1 of degree 0
+    let anf = algebraic_normal_form(&parse_formula("AB&C|DE&^FG|^HI&^"));
+    assert!(equivalent("AB&C|DE&^FG|^HI&^", &ast_to_rpn(&anf.to_node())));
+    
+    // ABCD&&&
+
+}
+
+
+
+
+
+
+
+//===================================== TSEITIN CNF =====================================
+//=======================================================================================
+
+// A clause is a list of literals, `v` is the variable v and `-v` its negation.
+// Variables 1 to 26 are the letters A to Z, the fresh ones are numbered after them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedCnf {
+    pub clauses: Vec<Vec<i32>>,
+    pub aux: Vec<(i32, Node)>, // fresh variable and the subformula it stands for
+    pub num_vars: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Polarity {
+    Positive,
+    Negative,
+    Both,
+}
+
+impl Polarity {
+    fn flip(self) -> Self {
+        match self {
+            Polarity::Positive => Polarity::Negative,
+            Polarity::Negative => Polarity::Positive,
+            Polarity::Both => Polarity::Both,
+        }
+    }
+}
+
+fn letter_var(c: char) -> i32 {
+    (c as u8 - b'A') as i32 + 1
+}
+
+fn var_name(var: i32) -> String {
+    if var <= 26 {
+        ((b'A' + (var - 1) as u8) as char).to_string()
+    } else {
+        format!("x{var}")
+    }
+}
+
+struct Encoder {
+    cnf: EncodedCnf,
+    polarity_aware: bool,
+}
+
+impl Encoder {
+    fn fresh(&mut self, node: &Node) -> i32 {
+        self.cnf.num_vars += 1;
+        self.cnf.aux.push((self.cnf.num_vars, node.clone()));
+        self.cnf.num_vars
+    }
+
+    // Clauses for x ↔ node, or only the direction the polarity of the node needs
+    // (x → node when it is only seen positively, node → x when only negatively)
+    fn gate(&mut self, polarity: Polarity, implies: Vec<Vec<i32>>, implied: Vec<Vec<i32>>) {
+        let polarity = if self.polarity_aware { polarity } else { Polarity::Both };
+        if polarity != Polarity::Negative {
+            self.cnf.clauses.extend(implies);
+        }
+        if polarity != Polarity::Positive {
+            self.cnf.clauses.extend(implied);
+        }
+    }
+
+    // Literal standing for the node, negations are free and do not need a variable
+    fn encode(&mut self, node: &Node, polarity: Polarity) -> i32 {
+        match node {
+            Node::Value(c) => letter_var(*c),
+            Node::Bool(val) => {
+                let x = self.fresh(node);
+                self.cnf.clauses.push(vec![if *val { x } else { -x }]);
+                x
+            }
+            Node::UnaryExpr { op: _, child } => -self.encode(child, polarity.flip()),
+            Node::BinaryExpr { op, lhs, rhs } => {
+                let (lhs_polarity, rhs_polarity) = match op {
+                    Conjunction | Disjunction => (polarity, polarity),
+                    MaterialCondition => (polarity.flip(), polarity),
+                    _ => (Polarity::Both, Polarity::Both),
+                };
+                let a = self.encode(lhs, lhs_polarity);
+                let b = self.encode(rhs, rhs_polarity);
+                let x = self.fresh(node);
+                match op {
+                    // x ↔ a ∧ b
+                    Conjunction => self.gate(polarity, vec![vec![-x, a], vec![-x, b]], vec![vec![-a, -b, x]]),
+                    // x ↔ a ∨ b
+                    Disjunction => self.gate(polarity, vec![vec![-x, a, b]], vec![vec![-a, x], vec![-b, x]]),
+                    // x ↔ ¬a ∨ b
+                    MaterialCondition => self.gate(polarity, vec![vec![-x, -a, b]], vec![vec![a, x], vec![-b, x]]),
+                    // x ↔ a ⊕ b
+                    ExclusiveDisjunction => self.gate(
+                        polarity,
+                        vec![vec![-x, a, b], vec![-x, -a, -b]],
+                        vec![vec![-a, b, x], vec![a, -b, x]],
+                    ),
+                    // x ↔ (a ↔ b), the same as x ↔ ¬a ⊕ b
+                    LogicalEquivalence => self.gate(
+                        polarity,
+                        vec![vec![-x, -a, b], vec![-x, a, -b]],
+                        vec![vec![a, b, x], vec![-a, -b, x]],
+                    ),
+                    Negation => panic!("Should not enter here"),
+                }
+                x
+            }
+        }
+    }
+}
+
+fn encode_cnf(node: &Node, polarity_aware: bool) -> EncodedCnf {
+    let mut encoder = Encoder { cnf: EncodedCnf { clauses: Vec::new(), aux: Vec::new(), num_vars: 26 }, polarity_aware };
+    let root = encoder.encode(node, Polarity::Positive);
+    encoder.cnf.clauses.push(vec![root]);
+    encoder.cnf
+}
+
+// Equisatisfiable CNF with one fresh variable per operator, linear in the size of the formula
+fn tseitin_cnf(node: &Node) -> EncodedCnf {
+    encode_cnf(node, false)
+}
+
+// Same, but each fresh variable only gets the clauses for the polarity it is used with
+fn plaisted_greenbaum_cnf(node: &Node) -> EncodedCnf {
+    encode_cnf(node, true)
+}
+
+fn clauses_to_string(clauses: &[Vec<i32>]) -> String {
+    clauses
+        .iter()
+        .map(|clause| {
+            let literals: Vec<String> = clause
+                .iter()
+                .map(|lit| if *lit < 0 { format!("¬{}", var_name(-lit)) } else { var_name(*lit) })
+                .collect();
+            format!("({})", literals.join(" ∨ "))
+        })
+        .collect::<Vec<String>>()
+        .join(" ∧ ")
+}
+
+fn print_encoded_cnf(cnf: &EncodedCnf) {
+    println!("{}", clauses_to_string(&cnf.clauses));
+    for (var, node) in &cnf.aux {
+        println!("  {} ≡ {}", var_name(*var), ast_to_rpn(node));
+    }
+}
+
+//=======================================================================================
+
+
+//==================================== MINIMIZATION =====================================
+//=======================================================================================
+
+// A product of literals over the letters of the formula. Bit i stands for the letter
+// n-1-i, so the first letter is the most significant bit like in the truth table lines,
+// and the bits set in `mask` are the letters left out of the product
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Implicant {
+    pub value: u32,
+    pub mask: u32,
+}
+
+impl Implicant {
+    fn covers(&self, row: u32) -> bool {
+        row & !self.mask == self.value
+    }
+
+    fn literals(&self, num_vars: usize) -> usize {
+        num_vars - self.mask.count_ones() as usize
+    }
+}
+
+fn evaluate_row(node: &Node, used_char: &[char], row: u32) -> bool {
+    match node {
+        Node::Value(c) => {
+            let i = used_char.iter().position(|used| used == c).expect("Letter missing from used_char");
+            row >> (used_char.len() - 1 - i) & 1 == 1
+        }
+        Node::Bool(val) => *val,
+        Node::UnaryExpr { op: _, child } => !evaluate_row(child, used_char, row),
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let left = evaluate_row(lhs, used_char, row);
+            let right = evaluate_row(rhs, used_char, row);
+            match op {
+                Conjunction => left & right,
+                Disjunction => left | right,
+                ExclusiveDisjunction => left ^ right,
+                MaterialCondition => !left | right,
+                LogicalEquivalence => left == right,
+                Negation => panic!("Should not enter here"),
+            }
+        }
+    }
+}
+
+// Letters of the formula and the truth table lines where it is true and where it is false
+fn minterms(formula: &str) -> (Vec<char>, Vec<u32>, Vec<u32>) {
+    let used_char = parse_formula_char(formula);
+    assert!(used_char.len() < 32, "Too many variables to minimize");
+    let root = parse_formula(formula);
+    let (on, off) = (0..1u32 << used_char.len()).partition(|row| evaluate_row(&root, &used_char, *row));
+    (used_char, on, off)
+}
+
+// Quine–McCluskey: merge the implicants that only differ by one letter until nothing
+// merges anymore, the ones that never merged are the primes. Each level keeps, for
+// every mask, a bitset of the values with an implicant so a whole column of the
+// table merges at once. The don't care lines can be merged with but need no cover
+fn prime_implicants(num_vars: usize, on: &[u32], dont_care: &[u32]) -> Vec<Implicant> {
+    // Lines with the letter of the bit false, for the letters inside a word
+    const CLEAR: [u64; 6] = [
+        0x5555_5555_5555_5555,
+        0x3333_3333_3333_3333,
+        0x0F0F_0F0F_0F0F_0F0F,
+        0x00FF_00FF_00FF_00FF,
+        0x0000_FFFF_0000_FFFF,
+        0x0000_0000_FFFF_FFFF,
+    ];
+    let num_words = (1usize << num_vars).div_ceil(64);
+    let mut lines = vec![0u64; num_words];
+    for row in on.iter().chain(dont_care) {
+        lines[*row as usize / 64] |= 1 << (row % 64);
+    }
+    let mut level: HashMap<u32, Vec<u64>> = HashMap::new();
+    level.insert(0, lines);
+    let mut primes = Vec::new();
+    while !level.is_empty() {
+        let mut next: HashMap<u32, Vec<u64>> = HashMap::new();
+        for (mask, values) in &level {
+            let mut merged = vec![0u64; num_words];
+            for bit in (0..num_vars).filter(|bit| mask & (1 << bit) == 0) {
+                // The partner of v is v + 2^bit, in the same word for the first six
+                // letters and 2^bit / 64 words further for the others
+                let shift = 1usize << bit;
+                let pairs: Vec<u64> = (0..num_words)
+                    .map(|i| match bit {
+                        0..=5 => values[i] & (values[i] >> shift) & CLEAR[bit],
+                        _ if i & (shift / 64) == 0 => values[i] & values[i + shift / 64],
+                        _ => 0,
+                    })
+                    .collect();
+                if pairs.iter().all(|word| *word == 0) {
+                    continue;
+                }
+                let target = next.entry(mask | (1 << bit)).or_insert_with(|| vec![0u64; num_words]);
+                for (i, pair) in pairs.iter().enumerate().filter(|(_, pair)| **pair != 0) {
+                    target[i] |= pair;
+                    merged[i] |= pair;
+                    if bit < 6 {
+                        merged[i] |= pair << shift;
+                    } else {
+                        merged[i + shift / 64] |= pair;
+                    }
+                }
+            }
+            for (i, word) in values.iter().enumerate() {
+                let mut unmerged = word & !merged[i];
+                while unmerged != 0 {
+                    let value = (i * 64) as u32 + unmerged.trailing_zeros();
+                    primes.push(Implicant { value, mask: *mask });
+                    unmerged &= unmerged - 1;
+                }
+            }
+        }
+        level = next;
+    }
+    primes.sort();
+    primes
+}
+
+// Covering table between the lines still to cover and the primes still usable, kept
+// both ways as sorted index lists into `on` and `primes`
+#[derive(Clone)]
+struct CoverTable {
+    row_cols: Vec<Vec<usize>>,
+    col_rows: Vec<Vec<usize>>,
+    row_active: Vec<bool>,
+    col_active: Vec<bool>,
+    literals: Vec<usize>,
+}
+
+fn sorted_subset(small: &[usize], big: &[usize]) -> bool {
+    let mut big = big.iter();
+    small.iter().all(|item| big.any(|other| other == item))
+}
+
+impl CoverTable {
+    fn new(num_vars: usize, primes: &[Implicant], on: &[u32]) -> Self {
+        let mut row_cols = vec![Vec::new(); on.len()];
+        let mut col_rows = vec![Vec::new(); primes.len()];
+        for (row, line) in on.iter().enumerate() {
+            for col in (0..primes.len()).filter(|col| primes[*col].covers(*line)) {
+                row_cols[row].push(col);
+                col_rows[col].push(row);
+            }
+        }
+        CoverTable {
+            row_cols,
+            col_rows,
+            row_active: vec![true; on.len()],
+            col_active: vec![true; primes.len()],
+            literals: primes.iter().map(|implicant| implicant.literals(num_vars)).collect(),
+        }
+    }
+
+    fn remove_row(&mut self, row: usize) {
+        self.row_active[row] = false;
+        for col in std::mem::take(&mut self.row_cols[row]) {
+            self.col_rows[col].retain(|other| *other != row);
+        }
+    }
+
+    fn remove_col(&mut self, col: usize) {
+        self.col_active[col] = false;
+        for row in std::mem::take(&mut self.col_rows[col]) {
+            self.row_cols[row].retain(|other| *other != col);
+        }
+    }
+
+    fn select(&mut self, col: usize, chosen: &mut Vec<usize>) {
+        for row in self.col_rows[col].clone() {
+            self.remove_row(row);
+        }
+        self.remove_col(col);
+        chosen.push(col);
+    }
+
+    // Takes the essential primes, then drops the lines that another line's cover also
+    // covers and the primes another one beats (more lines for no more literals),
+    // until nothing changes. False when a line cannot be covered anymore
+    fn reduce(&mut self, chosen: &mut Vec<usize>) -> bool {
+        loop {
+            let mut changed = false;
+            for row in 0..self.row_cols.len() {
+                if !self.row_active[row] {
+                    continue;
+                }
+                match self.row_cols[row].len() {
+                    0 => return false,
+                    1 => {
+                        self.select(self.row_cols[row][0], chosen);
+                        changed = true;
+                    }
+                    _ => {}
+                }
+            }
+            for row in 0..self.row_cols.len() {
+                if !self.row_active[row] {
+                    continue;
+                }
+                // A line holding the columns of `row` has them all, the rarest one included
+                let rarest = *self.row_cols[row].iter().min_by_key(|col| self.col_rows[**col].len()).expect("Uncovered line");
+                for other in self.col_rows[rarest].clone() {
+                    let (small, big) = (&self.row_cols[row], &self.row_cols[other]);
+                    if other != row && (small.len() < big.len() || row < other) && sorted_subset(small, big) {
+                        self.remove_row(other);
+                        changed = true;
+                    }
+                }
+            }
+            for col in 0..self.col_rows.len() {
+                if !self.col_active[col] {
+                    continue;
+                }
+                if self.col_rows[col].is_empty() {
+                    self.remove_col(col);
+                    changed = true;
+                    continue;
+                }
+                let rarest = *self.col_rows[col].iter().min_by_key(|row| self.row_cols[**row].len()).expect("Empty column");
+                let dominated = self.row_cols[rarest].iter().any(|other| {
+                    let (small, big) = (&self.col_rows[col], &self.col_rows[*other]);
+                    let tie = small.len() == big.len() && self.literals[col] == self.literals[*other];
+                    *other != col
+                        && self.literals[*other] <= self.literals[col]
+                        && (!tie || *other < col)
+                        && sorted_subset(small, big)
+                });
+                if dominated {
+                    self.remove_col(col);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return true;
+            }
+        }
+    }
+
+    // Lines sharing no prime each need one of their own, and at least its cheapest one
+    fn lower_bound(&self) -> (usize, usize) {
+        let mut rows: Vec<usize> = (0..self.row_cols.len()).filter(|row| self.row_active[*row]).collect();
+        rows.sort_by_key(|row| self.row_cols[*row].len());
+        let mut used = vec![false; self.col_rows.len()];
+        let (mut count, mut literals) = (0, 0);
+        for row in rows {
+            if self.row_cols[row].iter().any(|col| used[*col]) {
+                continue;
+            }
+            count += 1;
+            literals += self.row_cols[row].iter().map(|col| self.literals[*col]).min().unwrap_or(0);
+            for col in &self.row_cols[row] {
+                used[*col] = true;
+            }
+        }
+        (count, literals)
+    }
+}
+
+// Fewest products covering every line of `on`, and the fewest literals among those.
+// The table is reduced before every branch, so only its cyclic core is searched: one
+// branch per prime of the line with the fewest, each one leaving out the primes the
+// branches before it already tried
+fn minimum_cover(num_vars: usize, primes: &[Implicant], on: &[u32]) -> Vec<Implicant> {
+    type Best = Option<((usize, usize), Vec<usize>)>;
+
+    fn branch(mut table: CoverTable, mut chosen: Vec<usize>, best: &mut Best) {
+        if !table.reduce(&mut chosen) {
+            return;
+        }
+        let cost = (chosen.len(), chosen.iter().map(|col| table.literals[*col]).sum::<usize>());
+        let bound = table.lower_bound();
+        if best.as_ref().is_some_and(|(best, _)| (cost.0 + bound.0, cost.1 + bound.1) >= *best) {
+            return;
+        }
+        let Some(row) = (0..table.row_cols.len()).filter(|row| table.row_active[*row]).min_by_key(|row| table.row_cols[*row].len())
+        else {
+            *best = Some((cost, chosen));
+            return;
+        };
+        let mut candidates = table.row_cols[row].clone();
+        candidates.sort_by_key(|col| (std::cmp::Reverse(table.col_rows[*col].len()), table.literals[*col]));
+        for (i, col) in candidates.iter().enumerate() {
+            let mut next = table.clone();
+            for tried in &candidates[..i] {
+                next.remove_col(*tried);
+            }
+            let mut next_chosen = chosen.clone();
+            next.select(*col, &mut next_chosen);
+            branch(next, next_chosen, best);
+        }
+    }
+
+    let mut best = None;
+    branch(CoverTable::new(num_vars, primes, on), Vec::new(), &mut best);
+    let (_, chosen) = best.expect("No cover found");
+    chosen.into_iter().map(|col| primes[col]).collect()
+}
+
+fn minimize(num_vars: usize, on: &[u32], dont_care: &[u32]) -> Vec<Implicant> {
+    let primes = prime_implicants(num_vars, on, dont_care);
+    let mut cover = minimum_cover(num_vars, &primes, on);
+    // Products on the first letters first
+    cover.sort_by_key(|implicant| (std::cmp::Reverse(!implicant.mask), std::cmp::Reverse(implicant.value)));
+    cover
+}
+
+// `outer` of products, ∨ of ∧ for a sum of products. For a product of sums the
+// implicants come from the false lines, so every literal is negated on the way
+fn two_level(cover: &[Implicant], used_char: &[char], outer: Operator) -> Node {
+    let inner = if outer == Disjunction { Conjunction } else { Disjunction };
+    let terms: Vec<Node> = cover
+        .iter()
+        .map(|implicant| {
+            let literals: Vec<Node> = used_char
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| implicant.mask >> (used_char.len() - 1 - i) & 1 == 0)
+                .map(|(i, c)| {
+                    let set = implicant.value >> (used_char.len() - 1 - i) & 1 == 1;
+                    if set == (outer == Disjunction) {
+                        Value(*c)
+                    } else {
+                        Node::UnaryExpr { op: Negation, child: Box::new(Value(*c)) }
+                    }
+                })
+                .collect();
+            if literals.is_empty() { Bool(inner == Conjunction) } else { nest(&inner, literals) }
+        })
+        .collect();
+    if terms.is_empty() { Bool(outer == Conjunction) } else { nest(&outer, terms) }
+}
+
+// Minimum sum of products, in RPN
+fn minimize_sop(formula: &str) -> String {
+    let (used_char, on, _) = minterms(formula);
+    let cover = minimize(used_char.len(), &on, &[]);
+    ast_to_rpn(&two_level(&cover, &used_char, Disjunction))
+}
+
+// Minimum product of sums, the minimum sum of products of the negation turned around
+fn minimize_pos(formula: &str) -> String {
+    let (used_char, _, off) = minterms(formula);
+    let cover = minimize(used_char.len(), &off, &[]);
+    ast_to_rpn(&two_level(&cover, &used_char, Conjunction))
+}
+
+//=======================================================================================
+
+
+//======================================= ESPRESSO ======================================
+//=======================================================================================
+
+// Cube covers stay Implicant lists here, so up to 32 letters. Nothing walks the whole
+// truth table: the cover of the true lines is built cube by cube from the formula, and
+// the false lines are its complement
+
+impl Implicant {
+    fn contains(&self, other: &Implicant) -> bool {
+        other.mask & !self.mask == 0 && (self.value ^ other.value) & !self.mask == 0
+    }
+
+    fn intersects(&self, other: &Implicant) -> bool {
+        (self.value ^ other.value) & !self.mask & !other.mask == 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EspressoMode {
+    Deterministic,
+    Randomized { restarts: usize, seed: u64 }, // shuffled expansion orders, keeps the best cover
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CoverReport {
+    pub products: usize,
+    pub literals: usize,
+    pub canonical_products: u64, // one product per true line of the truth table
+    pub canonical_literals: u64,
+}
+
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.0 >> 33
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.next() as usize % (i + 1));
+        }
+    }
+}
+
+fn full_mask(num_vars: usize) -> u32 {
+    if num_vars == 32 { u32::MAX } else { (1 << num_vars) - 1 }
+}
+
+fn cover_cost(num_vars: usize, cover: &[Implicant]) -> (usize, usize) {
+    (cover.len(), cover.iter().map(|cube| cube.literals(num_vars)).sum())
+}
+
+// The cubes restricted to the half where the letter of `bit` is `val`
+fn cofactor(cubes: &[Implicant], bit: usize, val: bool) -> Vec<Implicant> {
+    cubes
+        .iter()
+        .filter(|cube| cube.mask >> bit & 1 == 1 || (cube.value >> bit & 1 == 1) == val)
+        .map(|cube| Implicant { value: cube.value & !(1 << bit), mask: cube.mask | 1 << bit })
+        .collect()
+}
+
+// The cubes restricted to `cube`
+fn cofactor_cube(num_vars: usize, cubes: &[Implicant], cube: &Implicant) -> Vec<Implicant> {
+    let fixed = !cube.mask & full_mask(num_vars);
+    cubes
+        .iter()
+        .filter(|other| other.intersects(cube))
+        .map(|other| Implicant { value: other.value & !fixed, mask: other.mask | fixed })
+        .collect()
+}
+
+// Letter fixed in the most cubes, and whether it shows up with both signs
+fn splitting_var(num_vars: usize, cubes: &[Implicant]) -> Option<(usize, bool)> {
+    (0..num_vars)
+        .filter_map(|bit| {
+            let fixed = cubes.iter().filter(|cube| cube.mask >> bit & 1 == 0);
+            let ones = fixed.clone().filter(|cube| cube.value >> bit & 1 == 1).count();
+            let total = fixed.count();
+            (total > 0).then_some((bit, ones > 0 && ones < total, total))
+        })
+        .max_by_key(|(bit, binate, total)| (*binate, *total, std::cmp::Reverse(*bit)))
+        .map(|(bit, binate, _)| (bit, binate))
+}
+
+// Unate recursion: split on a letter until the cover has the universal cube, or until
+// every letter only shows up with one sign, then it cannot be a tautology anymore
+fn tautology(num_vars: usize, cubes: &[Implicant]) -> bool {
+    let full = full_mask(num_vars);
+    if cubes.iter().any(|cube| cube.mask & full == full) {
+        return true;
+    }
+    match splitting_var(num_vars, cubes) {
+        Some((bit, true)) => {
+            tautology(num_vars, &cofactor(cubes, bit, false)) && tautology(num_vars, &cofactor(cubes, bit, true))
+        }
+        _ => false,
+    }
+}
+
+// Drops the cubes written twice or inside a bigger one
+fn absorb(mut cubes: Vec<Implicant>) -> Vec<Implicant> {
+    cubes.sort_by_key(|cube| std::cmp::Reverse(cube.mask.count_ones()));
+    let mut kept: Vec<Implicant> = Vec::new();
+    for cube in cubes {
+        if !kept.iter().any(|other| other.contains(&cube)) {
+            kept.push(cube);
+        }
+    }
+    kept
+}
+
+// Every nonempty intersection of a cube of lhs with a cube of rhs
+fn cube_product(lhs: &[Implicant], rhs: &[Implicant]) -> Vec<Implicant> {
+    let cubes = lhs
+        .iter()
+        .flat_map(|a| rhs.iter().filter(|b| a.intersects(b)).map(|b| Implicant { value: a.value | b.value, mask: a.mask & b.mask }))
+        .collect();
+    absorb(cubes)
+}
+
+// Unate recursion again: one cube complements by De Morgan, otherwise split on a letter,
+// complement both halves and put the letter back on the cubes only one half has
+fn complement(num_vars: usize, cubes: &[Implicant]) -> Vec<Implicant> {
+    let full = full_mask(num_vars);
+    if cubes.iter().any(|cube| cube.mask & full == full) {
+        return Vec::new();
+    }
+    match cubes {
+        [] => vec![Implicant { value: 0, mask: full }],
+        [cube] => (0..num_vars)
+            .filter(|bit| cube.mask >> bit & 1 == 0)
+            .map(|bit| Implicant { value: !cube.value & 1 << bit, mask: full & !(1 << bit) })
+            .collect(),
+        _ => {
+            let (bit, _) = splitting_var(num_vars, cubes).expect("Cubes without letters");
+            let low = complement(num_vars, &cofactor(cubes, bit, false));
+            let high = complement(num_vars, &cofactor(cubes, bit, true));
+            let mut result: Vec<Implicant> = low
+                .iter()
+                .map(|cube| if high.contains(cube) { *cube } else { Implicant { value: cube.value, mask: cube.mask & !(1 << bit) } })
+                .collect();
+            result.extend(
+                high.iter().filter(|cube| !low.contains(cube)).map(|cube| Implicant { value: cube.value | 1 << bit, mask: cube.mask & !(1 << bit) }),
+            );
+            absorb(result)
+        }
+    }
+}
+
+fn covered(num_vars: usize, cube: &Implicant, cubes: &[Implicant]) -> bool {
+    tautology(num_vars, &cofactor_cube(num_vars, cubes, cube))
+}
+
+// Number of lines the cubes cover among the 2^free left once the split letters are gone
+fn count_minterms(num_vars: usize, cubes: &[Implicant], free: usize) -> u64 {
+    let full = full_mask(num_vars);
+    match cubes {
+        [] => 0,
+        [cube] => 1 << (free - (!cube.mask & full).count_ones() as usize),
+        _ if cubes.iter().any(|cube| cube.mask & full == full) => 1 << free,
+        _ => {
+            let (bit, _) = splitting_var(num_vars, cubes).expect("Cubes without letters");
+            count_minterms(num_vars, &cofactor(cubes, bit, false), free - 1)
+                + count_minterms(num_vars, &cofactor(cubes, bit, true), free - 1)
+        }
+    }
+}
+
+// Grow every cube as far as the false lines let it, dropping the cubes it swallows.
+// Biggest cubes first, raising first the letters most of the cover already leaves out
+fn expand(num_vars: usize, cover: Vec<Implicant>, off: &[Implicant], rng: &mut Option<Lcg>) -> Vec<Implicant> {
+    let mut cubes = cover;
+    let mut bits: Vec<usize> = (0..num_vars).collect();
+    bits.sort_by_key(|bit| std::cmp::Reverse(cubes.iter().filter(|cube| cube.mask >> bit & 1 == 1).count()));
+    cubes.sort_by_key(|cube| (cube.literals(num_vars), cube.value));
+    if let Some(rng) = rng {
+        rng.shuffle(&mut bits);
+        rng.shuffle(&mut cubes);
+    }
+    let mut expanded: Vec<Implicant> = Vec::new();
+    for mut cube in cubes {
+        if expanded.iter().any(|other| other.contains(&cube)) {
+            continue;
+        }
+        for bit in &bits {
+            if cube.mask >> bit & 1 == 1 {
+                continue;
+            }
+            let raised = Implicant { value: cube.value & !(1 << bit), mask: cube.mask | 1 << bit };
+            if !off.iter().any(|other| raised.intersects(other)) {
+                cube = raised;
+            }
+        }
+        expanded.retain(|other| !cube.contains(other));
+        expanded.push(cube);
+    }
+    expanded
+}
+
+// Drop the cubes the rest of the cover (and the don't cares) already cover, smallest first
+fn irredundant(num_vars: usize, cover: Vec<Implicant>, dont_care: &[Implicant]) -> Vec<Implicant> {
+    let mut cover = cover;
+    cover.sort_by_key(|cube| (std::cmp::Reverse(cube.literals(num_vars)), cube.value));
+    let mut i = 0;
+    while i < cover.len() {
+        let others: Vec<Implicant> = cover.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, cube)| *cube).chain(dont_care.iter().copied()).collect();
+        if covered(num_vars, &cover[i], &others) {
+            cover.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    cover
+}
+
+// Shrink every cube to the half the rest of the cover does not already cover, so the
+// next expansion can take it somewhere else
+fn reduce(num_vars: usize, cover: Vec<Implicant>, dont_care: &[Implicant]) -> Vec<Implicant> {
+    let mut cover = cover;
+    cover.sort_by_key(|cube| (cube.literals(num_vars), cube.value));
+    for i in 0..cover.len() {
+        let others: Vec<Implicant> = cover.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, cube)| *cube).chain(dont_care.iter().copied()).collect();
+        let mut cube = cover[i];
+        for bit in (0..num_vars).filter(|bit| cover[i].mask >> bit & 1 == 1) {
+            let low = Implicant { value: cube.value, mask: cube.mask & !(1 << bit) };
+            let high = Implicant { value: cube.value | 1 << bit, mask: low.mask };
+            if covered(num_vars, &low, &others) {
+                cube = high;
+            } else if covered(num_vars, &high, &others) {
+                cube = low;
+            }
+        }
+        cover[i] = cube;
+    }
+    cover
+}
+
+// Expand, irredundant, then reduce and go again while the cover keeps getting cheaper
+fn espresso_pass(num_vars: usize, on: &[Implicant], dont_care: &[Implicant], off: &[Implicant], rng: &mut Option<Lcg>) -> Vec<Implicant> {
+    let mut cover = irredundant(num_vars, expand(num_vars, on.to_vec(), off, rng), dont_care);
+    loop {
+        let next = reduce(num_vars, cover.clone(), dont_care);
+        let next = irredundant(num_vars, expand(num_vars, next, off, rng), dont_care);
+        if cover_cost(num_vars, &next) >= cover_cost(num_vars, &cover) {
+            return cover;
+        }
+        cover = next;
+    }
+}
+
+fn espresso(num_vars: usize, on: &[Implicant], dont_care: &[Implicant], off: &[Implicant], mode: EspressoMode) -> Vec<Implicant> {
+    let mut cover = match mode {
+        EspressoMode::Deterministic => espresso_pass(num_vars, on, dont_care, off, &mut None),
+        EspressoMode::Randomized { restarts, seed } => {
+            let mut rng = Some(Lcg(seed));
+            (0..restarts.max(1))
+                .map(|_| espresso_pass(num_vars, on, dont_care, off, &mut rng))
+                .min_by_key(|cover| cover_cost(num_vars, cover))
+                .expect("No pass ran")
+        }
+    };
+    cover.sort_by_key(|cube| (std::cmp::Reverse(!cube.mask), std::cmp::Reverse(cube.value)));
+    cover
+}
+
+// Cover of the true lines straight from the formula: every ∧ and ∨ works on cube lists
+// already free of contradictions and absorbed cubes, and a negation is a complement
+fn formula_cover(node: &Node, used_char: &[char]) -> Vec<Implicant> {
+    let num_vars = used_char.len();
+    match node {
+        Node::Value(c) => {
+            let bit = num_vars - 1 - used_char.iter().position(|used| used == c).expect("Letter missing from used_char");
+            vec![Implicant { value: 1 << bit, mask: full_mask(num_vars) & !(1 << bit) }]
+        }
+        Node::Bool(true) => vec![Implicant { value: 0, mask: full_mask(num_vars) }],
+        Node::Bool(false) => Vec::new(),
+        Node::UnaryExpr { op: _, child } => complement(num_vars, &formula_cover(child, used_char)),
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let a = formula_cover(lhs, used_char);
+            let b = formula_cover(rhs, used_char);
+            let union = |lhs: Vec<Implicant>, rhs: Vec<Implicant>| absorb(lhs.into_iter().chain(rhs).collect());
+            match op {
+                Conjunction => cube_product(&a, &b),
+                Disjunction => union(a, b),
+                MaterialCondition => union(complement(num_vars, &a), b),
+                ExclusiveDisjunction => {
+                    let (not_a, not_b) = (complement(num_vars, &a), complement(num_vars, &b));
+                    union(cube_product(&a, &not_b), cube_product(&not_a, &b))
+                }
+                LogicalEquivalence => {
+                    let (not_a, not_b) = (complement(num_vars, &a), complement(num_vars, &b));
+                    union(cube_product(&a, &b), cube_product(&not_a, &not_b))
+                }
+                Negation => panic!("Should not enter here"),
+            }
         }
     }
-    used_char
 }
 
-//=======================================================================================
+fn cover_report(num_vars: usize, cover: &[Implicant], on: &[Implicant]) -> CoverReport {
+    let (products, literals) = cover_cost(num_vars, cover);
+    let canonical_products = count_minterms(num_vars, on, num_vars);
+    CoverReport { products, literals, canonical_products, canonical_literals: canonical_products * num_vars as u64 }
+}
 
+// Heuristic sum of products in RPN, with its size against the canonical form
+fn espresso_formula(formula: &str, mode: EspressoMode) -> (String, CoverReport) {
+    let used_char = parse_formula_char(formula);
+    assert!(used_char.len() <= 32, "Too many variables to minimize");
+    let on = formula_cover(&parse_formula(formula), &used_char);
+    let off = complement(used_char.len(), &on);
+    let cover = espresso(used_char.len(), &on, &[], &off, mode);
+    (ast_to_rpn(&two_level(&cover, &used_char, Disjunction)), cover_report(used_char.len(), &cover, &on))
+}
 
-fn equivalence(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::LogicalEquivalence, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(equivalence(*lhs));
-            let rhs = Box::new(equivalence(*rhs));
+// Same from a truth table, line i being the value for row i
+fn espresso_table(used_char: &[char], table: &[bool], mode: EspressoMode) -> (String, CoverReport) {
+    assert!(table.len() == 1 << used_char.len(), "The table does not match the letters");
+    let (on, off): (Vec<Implicant>, Vec<Implicant>) = (0..table.len() as u32)
+        .map(|row| Implicant { value: row, mask: 0 })
+        .partition(|cube| table[cube.value as usize]);
+    let cover = espresso(used_char.len(), &on, &[], &off, mode);
+    (ast_to_rpn(&two_level(&cover, used_char, Disjunction)), cover_report(used_char.len(), &cover, &on))
+}
 
-            // Contruct the Material condition of lhs with rhs
-            let left_and = Node::BinaryExpr {
-                op: Operator::MaterialCondition,
-                lhs: lhs.clone(),
-                rhs: rhs.clone(),
-            };
+fn print_cover_report(report: &CoverReport) {
+    println!(
+        "{} products and {} literals, against {} products and {} literals in canonical form",
+        report.products, report.literals, report.canonical_products, report.canonical_literals
+    );
+}
 
-            // Contruct the Material condition of rhs with lhs
-            let right_and = Node::BinaryExpr {
-                op: Operator::MaterialCondition,
-                lhs: rhs.clone(),
-                rhs: lhs.clone(),
-            };
+//=======================================================================================
 
-            // Return the Conjunction of the left and right conditions
-            Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: Box::new(left_and),
-                rhs: Box::new(right_and),
-            }
+
+//=================================== PRIME IMPLICANTS ==================================
+//=======================================================================================
+
+impl Implicant {
+    // The cube both imply through the one letter they disagree on, xA ∨ y¬A ⊨ xy
+    fn consensus(&self, other: &Implicant, num_vars: usize) -> Option<Implicant> {
+        let opposed = (self.value ^ other.value) & !self.mask & !other.mask;
+        if opposed.count_ones() != 1 {
+            return None;
         }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(equivalence(*lhs)),
-            rhs: Box::new(equivalence(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(equivalence(*child)),
-        },
-        other => other,
+        let fixed = (!self.mask | !other.mask) & !opposed & full_mask(num_vars);
+        Some(Implicant { value: (self.value | other.value) & fixed, mask: !fixed & full_mask(num_vars) })
     }
 }
 
+// Iterated consensus: add the consensus of every pair until nothing new comes up,
+// dropping the cubes another one contains. What is left are all the primes
+fn consensus_primes(num_vars: usize, cubes: &[Implicant]) -> Vec<Implicant> {
+    let mut cover: Vec<Implicant> = Vec::new();
+    let mut pending: Vec<Implicant> = cubes.to_vec();
+    while let Some(cube) = pending.pop() {
+        if cover.iter().any(|other| other.contains(&cube)) {
+            continue;
+        }
+        cover.retain(|other| !cube.contains(other));
+        pending.extend(cover.iter().filter_map(|other| cube.consensus(other, num_vars)));
+        cover.push(cube);
+    }
+    cover.sort_by_key(|cube| (std::cmp::Reverse(!cube.mask), std::cmp::Reverse(cube.value)));
+    cover
+}
 
+fn formula_primes(formula: &str) -> (Vec<char>, Vec<Implicant>) {
+    let used_char = parse_formula_char(formula);
+    assert!(used_char.len() <= 32, "Too many variables");
+    let primes = consensus_primes(used_char.len(), &formula_cover(&parse_formula(formula), &used_char));
+    (used_char, primes)
+}
 
-fn material_conditon(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::MaterialCondition, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(material_conditon(*lhs));
-            let rhs = Box::new(material_conditon(*rhs));
+// The ∨ of every prime implicant
+fn blake_canonical_form(formula: &str) -> String {
+    let (used_char, primes) = formula_primes(formula);
+    ast_to_rpn(&two_level(&primes, &used_char, Disjunction))
+}
 
-            // Construct (¬lhs ∧ rhs)
-            Node::BinaryExpr {
-                op: Operator::Disjunction,
-                lhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: lhs,
-                }),
-                rhs,
-            }
-        }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(material_conditon(*lhs)),
-            rhs: Box::new(material_conditon(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(material_conditon(*child)),
-        },
-        other => other,
+// The ∧ of every prime implicate, the negations of the prime implicants of the negation
+fn prime_implicates(formula: &str) -> String {
+    let used_char = parse_formula_char(formula);
+    let (negated_char, primes) = formula_primes(&format!("{formula}!"));
+    assert!(negated_char == used_char, "Letters moved around");
+    ast_to_rpn(&two_level(&primes, &used_char, Conjunction))
+}
+
+// Each prime implicant, and whether it is essential: the only one covering some line
+fn classify_prime_implicants(formula: &str) -> Vec<(String, bool)> {
+    let (used_char, primes) = formula_primes(formula);
+    primes
+        .iter()
+        .enumerate()
+        .map(|(i, prime)| {
+            let others: Vec<Implicant> = primes.iter().enumerate().filter(|(j, _)| *j != i).map(|(_, other)| *other).collect();
+            let essential = !covered(used_char.len(), prime, &others);
+            (ast_to_rpn(&two_level(&[*prime], &used_char, Disjunction)), essential)
+        })
+        .collect()
+}
+
+fn print_prime_implicants(formula: &str) {
+    for (implicant, essential) in classify_prime_implicants(formula) {
+        println!("{implicant}{}", if essential { " essential" } else { "" });
     }
 }
 
+//=======================================================================================
 
-fn remove_xor(node: Node) -> Node {
-    match node {
-        Node::BinaryExpr { op: Operator::ExclusiveDisjunction, lhs, rhs } => {
-            // Recursively expand children first
-            let lhs = Box::new(remove_xor(*lhs));
-            let rhs = Box::new(remove_xor(*rhs));
 
-            // Construct (lhs ∧ ¬rhs)
-            let left_and = Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: lhs.clone(),
-                rhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: rhs.clone(),
-                }),
-            };
+//================================ ALGEBRAIC NORMAL FORM ================================
+//=======================================================================================
 
-            // Construct (¬lhs ∧ rhs)
-            let right_and = Node::BinaryExpr {
-                op: Operator::Conjunction,
-                lhs: Box::new(Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: lhs,
-                }),
-                rhs,
-            };
+// Zhegalkin polynomial: a ⊕ of monomials, each an ∧ of letters. Bits of a monomial
+// are letters like in Implicant, the empty monomial is the constant 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anf {
+    pub used_char: Vec<char>,
+    pub monomials: Vec<u32>,
+}
 
-            // Return (lhs ∧ ¬rhs) ∨ (¬lhs ∧ rhs)
-            Node::BinaryExpr {
-                op: Operator::Disjunction,
-                lhs: Box::new(left_and),
-                rhs: Box::new(right_and),
+// Möbius transform of the truth table: once every letter is folded in, line m holds
+// the coefficient of the monomial m
+fn algebraic_normal_form(node: &Node) -> Anf {
+    let used_char = parse_formula_char(&ast_to_rpn(node));
+    assert!(used_char.len() <= 24, "Too many variables for the truth table");
+    let mut table: Vec<bool> = (0..1u32 << used_char.len()).map(|row| evaluate_row(node, &used_char, row)).collect();
+    for bit in 0..used_char.len() {
+        for row in 0..table.len() {
+            if row >> bit & 1 == 1 {
+                table[row] ^= table[row ^ 1 << bit];
             }
         }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(remove_xor(*lhs)),
-            rhs: Box::new(remove_xor(*rhs)),
-        },
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(remove_xor(*child)),
-        },
-        other => other,
     }
+    let mut monomials: Vec<u32> = (0..table.len() as u32).filter(|monomial| table[*monomial as usize]).collect();
+    monomials.sort_by_key(|monomial| (monomial.count_ones(), std::cmp::Reverse(*monomial)));
+    Anf { used_char, monomials }
 }
 
+impl Anf {
+    // Most letters in one monomial, 0 for the constants
+    fn degree(&self) -> u32 {
+        self.monomials.iter().map(|monomial| monomial.count_ones()).max().unwrap_or(0)
+    }
 
-fn de_morgans_law(node: Node) -> Node{
-    match node {
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(de_morgans_law(*lhs)),
-            rhs: Box::new(de_morgans_law(*rhs)),
-        },
-        Node::UnaryExpr { op: Negation, child } =>{
-            match *child{
-                Node::BinaryExpr {op: Operator::Conjunction, lhs, rhs} => {
-                    Node::BinaryExpr {
-                        op: Operator::Disjunction,
-                        lhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*lhs),
-                        }),
-                        rhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*rhs),
-                        }),
-                    }
-                }
-                Node::BinaryExpr {op: Operator::Disjunction, lhs, rhs} => {
-                    Node::BinaryExpr {
-                        op: Operator::Conjunction,
-                        lhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*lhs),
-                        }),
-                        rhs: Box::new(Node::UnaryExpr {
-                            op: Operator::Negation,
-                            child: Box::new(*rhs),
-                        }),
-                    }
-                }
-                other => Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: Box::new(other),
-                }
-            }
-        }
-        other => other,
+    fn to_node(&self) -> Node {
+        let n = self.used_char.len();
+        let terms: Vec<Node> = self
+            .monomials
+            .iter()
+            .map(|monomial| {
+                let letters: Vec<Node> =
+                    (0..n).filter(|i| monomial >> (n - 1 - i) & 1 == 1).map(|i| Value(self.used_char[i])).collect();
+                if letters.is_empty() { Bool(true) } else { nest(&Conjunction, letters) }
+            })
+            .collect();
+        if terms.is_empty() { Bool(false) } else { nest(&ExclusiveDisjunction, terms) }
     }
 }
 
+fn print_anf(formula: &str) {
+    let anf = algebraic_normal_form(&parse_formula(formula));
+    println!("{} of degree {}", ast_to_rpn(&anf.to_node()), anf.degree());
+}
 
+//=======================================================================================
 
-// last step removing the double negations
-fn double_negation(node: Node) -> Node {
-     match node {
-        Node::UnaryExpr { op: Operator::Negation, child} => {
-            match *child {
-                Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: inner,
-                } => {
-                    *inner // Double negation: remove both
-                }
-                other => Node::UnaryExpr {
-                    op: Operator::Negation,
-                    child: Box::new(other),
-                },
-            }
-        }
-        Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
-            op,
-            lhs: Box::new(double_negation(*lhs)),
-            rhs: Box::new(double_negation(*rhs)),
-        },
-        other => other
-    }
-}
 
-fn distributivity(node: Node) -> Node{
-    match node {
-        Node::BinaryExpr {op: Operator::Disjunction, lhs, rhs} => {
-            if let Node::BinaryExpr { op: Operator::Conjunction, lhs: left, rhs: right} = *lhs {
-               // Recursively expand children first
-               let lhs = Box::new(*left);
-               let rhs = Box::new(*right);
-   
-               // Contruct the Material condition of lhs with rhs
-               let left_and = Node::BinaryExpr {
-                   op: Operator::MaterialCondition,
-                   lhs: lhs.clone(),
-                   rhs: rhs.clone(),
-               };
-   
-               // Contruct the Material condition of rhs with lhs
-               let right_and = Node::BinaryExpr {
-                   op: Operator::MaterialCondition,
-                   lhs: rhs.clone(),
-                   rhs: lhs.clone(),
-               };
-   
-               // Return the Conjunction of the left and right conditions
-               Node::BinaryExpr {
-                   op: Operator::Conjunction,
-                   lhs: Box::new(left_and),
-                   rhs: Box::new(right_and),
-               }
-            } else if let Node::BinaryExpr { op: Operator::Conjunction, lhs: left, rhs: right} = *rhs{
-                // Recursively expand children first
-                let lhs = Box::new(equivalence(*left));
-                let rhs = Box::new(equivalence(*right));
-
-                // Contruct the Material condition of lhs with rhs
-                let left_and = Node::BinaryExpr {
-                   op: Operator::MaterialCondition,
-                   lhs: lhs.clone(),
-                   rhs: rhs.clone(),
-                };
+//===================================== DON'T CARES =====================================
+//=======================================================================================
 
-                // Contruct the Material condition of rhs with lhs
-                let right_and = Node::BinaryExpr {
-                   op: Operator::MaterialCondition,
-                   lhs: rhs.clone(),
-                   rhs: lhs.clone(),
-                };
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Zero,
+    One,
+    DontCare, // never needs covering, only taken when it makes a group bigger
+}
 
-                // Return the Conjunction of the left and right conditions
-                Node::BinaryExpr {
-                   op: Operator::Conjunction,
-                   lhs: Box::new(left_and),
-                   rhs: Box::new(right_and),
-                }
-            } else{
-                Node::BinaryExpr {
-                    op: Disjunction,
-                    lhs: Box::new(distributivity(*lhs)),
-                    rhs: Box::new(distributivity(*rhs)),
-                }
+// Truth table with unspecified lines, cell i is line i over used_char (the first
+// letter being the most significant bit), letters in alphabetical order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncompleteFunction {
+    pub used_char: Vec<char>,
+    pub cells: Vec<Cell>,
+}
+
+impl IncompleteFunction {
+    // The lines where `dont_care` is true are unspecified, whatever `formula` gives
+    fn from_formulas(formula: &str, dont_care: &str) -> Self {
+        let mut used_char = parse_formula_char(formula);
+        for c in parse_formula_char(dont_care) {
+            if !used_char.contains(&c) {
+                used_char.push(c);
             }
         }
-        Node::UnaryExpr { op, child } => Node::UnaryExpr {
-            op,
-            child: Box::new(distributivity(*child)),
-        },
-        Node::BinaryExpr {op, lhs, rhs} => Node::BinaryExpr {
-            op,
-            lhs: Box::new(distributivity(*lhs)),
-            rhs: Box::new(distributivity(*rhs)),
-        },
-        other => other,
+        used_char.sort();
+        assert!(used_char.len() < 32, "Too many variables to minimize");
+        let (root, dont_care) = (parse_formula(formula), parse_formula(dont_care));
+        let cells = (0..1u32 << used_char.len())
+            .map(|row| match (evaluate_row(&dont_care, &used_char, row), evaluate_row(&root, &used_char, row)) {
+                (true, _) => Cell::DontCare,
+                (false, true) => Cell::One,
+                (false, false) => Cell::Zero,
+            })
+            .collect();
+        IncompleteFunction { used_char, cells }
     }
-}
 
-fn do_all(node: Node) -> Node {
-    distributivity(double_negation(de_morgans_law(material_conditon(equivalence(remove_xor(node))))))
-}
+    fn from_minterms(used_char: &[char], minterms: &[u32], dont_care: &[u32]) -> Self {
+        assert!(used_char.len() < 32, "Too many variables to minimize");
+        let mut cells = vec![Cell::Zero; 1 << used_char.len()];
+        for row in minterms.iter().chain(dont_care) {
+            assert!((*row as usize) < cells.len(), "Line {row} is outside of the truth table");
+        }
+        for row in minterms {
+            cells[*row as usize] = Cell::One;
+        }
+        for row in dont_care {
+            assert!(cells[*row as usize] != Cell::One, "Line {row} is both a minterm and a don't care");
+            cells[*row as usize] = Cell::DontCare;
+        }
+        IncompleteFunction { used_char: used_char.to_vec(), cells }
+    }
 
+    fn lines(&self, cell: Cell) -> Vec<u32> {
+        (0..self.cells.len() as u32).filter(|row| self.cells[*row as usize] == cell).collect()
+    }
 
-fn conjunctive_normal_form(formula: &str) -> String{
-    // let mut used_char = parse_formula_char(formula);
-    // if used_char.len() >= 2 && used_char.len() <= 4 {
-    //     if used_char.len() == 4 {
-    //         println!("K-MAP: {}", karnaugh_map4(formula, &mut used_char));
-    //     }
-    //     if used_char.len() == 3 {
-    //         println!("K-MAP: {}", karnaugh_map3(formula, &mut used_char));
-    //     }
-    //     if used_char.len() == 2 {
-    //         println!("K-MAP: {}", karnaugh_map2(formula, &mut used_char));
-    //     }
-    //     
-    // }
-    let mut original = parse_formula(formula);
-    let mut root = do_all(parse_formula(formula));
-    while original != root {
-        original = root.clone();
-        root = do_all(root);
+    fn minimize_sop(&self) -> String {
+        let cover = minimize(self.used_char.len(), &self.lines(Cell::One), &self.lines(Cell::DontCare));
+        ast_to_rpn(&two_level(&cover, &self.used_char, Disjunction))
+    }
+
+    fn minimize_pos(&self) -> String {
+        let cover = minimize(self.used_char.len(), &self.lines(Cell::Zero), &self.lines(Cell::DontCare));
+        ast_to_rpn(&two_level(&cover, &self.used_char, Conjunction))
+    }
+
+    // The groups are the ones of minimize_sop, don't cares show up as -
+    fn karnaugh_map(&self) -> String {
+        assert!(self.used_char.len() <= 6, "Karnaugh maps only go up to 6 variables");
+        let groups = minimize(self.used_char.len(), &self.lines(Cell::One), &self.lines(Cell::DontCare));
+        render_kmap(&self.used_char, &self.cells, &groups)
     }
-    ast_to_rpn(&root)
 }
 
+//=======================================================================================
 
 
-fn main() {
-    println!("{}", conjunctive_normal_form("AB&!"));
-    // A!B!|
-    println!("{}", conjunctive_normal_form("AB|!"));
-    // A!B!&
-    println!("{}", conjunctive_normal_form("AB|C&"));
-    // AB|C&
-    println!("{}", conjunctive_normal_form("AB|C|D|"));
-    // ABCD|||
-    println!("{}", conjunctive_normal_form("AB&!C!|"));
-    // A!B!C!||
-    println!("{}", conjunctive_normal_form("AB|!C!&"));
-    // A!B!C!&&
-    println!("{}", conjunctive_normal_form("AB&C&D&"));
-    println!("{}", conjunctive_normal_form("A!B&C&D&B&"));
-    println!("{}", conjunctive_normal_form("BCD!A!&&&"));
-    println!("{}", conjunctive_normal_form("AB|C!D|&"));
-    println!("{}", conjunctive_normal_form("CD^A!A|&B!B|&"));
-    println!("{}", conjunctive_normal_form("AC|BC|AD|BD|&&&"));
-    println!("{}", conjunctive_normal_form("AB|D|BC|D!|AC!|D|BC!|D|&&&"));
-    println!("{}", conjunctive_normal_form("ABCD|||"));
-    println!("{}", conjunctive_normal_form("B!C!|A!C|D!|ABCD|||&&"));
-    
-    // ABCD&&&
+//=================================== KARNAUGH MAPS =====================================
+//=======================================================================================
 
+fn group_tag(i: usize) -> char {
+    if i < 26 { (b'a' + i as u8) as char } else { (b'A' + (i - 26) as u8) as char }
 }
 
+// Up to 6 letters, the ones past the fourth choose one of the overlaid 4×4 planes.
+// Rows, columns and planes follow the Gray code so neighbouring cells, around the
+// edges too, only differ by one letter, and each cell lists the groups holding it
+fn karnaugh_map(formula: &str) -> String {
+    IncompleteFunction::from_formulas(formula, "0").karnaugh_map()
+}
 
+fn render_kmap(used_char: &[char], table: &[Cell], groups: &[Implicant]) -> String {
+    let n = used_char.len();
+    let plane_vars = n.saturating_sub(4);
+    let row_vars = (n - plane_vars) / 2;
+    let col_vars = n - plane_vars - row_vars;
+    let letters = |from: usize, count: usize| used_char[from..from + count].iter().collect::<String>();
+    let label = |index: usize, width: usize| if width == 0 { String::new() } else { format!("{:0width$b}", gray_code(index as u8)) };
+    let cell = |row: usize| {
+        let mut cell = match table[row] {
+            Cell::Zero => "0".to_string(),
+            Cell::One => "1".to_string(),
+            Cell::DontCare => "-".to_string(),
+        };
+        cell.extend(groups.iter().enumerate().filter(|(_, group)| group.covers(row as u32)).map(|(i, _)| group_tag(i)));
+        cell
+    };
+    let width = (0..table.len()).map(|row| cell(row).len()).max().unwrap_or(1).max(col_vars);
+    let corner = format!("{}\\{}", letters(plane_vars, row_vars), letters(plane_vars + row_vars, col_vars));
+    let corner_width = corner.len();
+
+    let mut out = String::new();
+    for plane in 0..1usize << plane_vars {
+        if plane_vars > 0 {
+            out.push_str(&format!("{} = {}\n", letters(0, plane_vars), label(plane, plane_vars)));
+        }
+        out.push_str(&format!("{corner:>corner_width$} │"));
+        for col in 0..1usize << col_vars {
+            out.push_str(&format!(" {:<width$} │", label(col, col_vars)));
+        }
+        out.push('\n');
+        out.push_str(&format!("{}┼", "─".repeat(corner_width + 1)));
+        let separators: Vec<String> = (0..1usize << col_vars).map(|_| "─".repeat(width + 2)).collect();
+        out.push_str(&format!("{}┤\n", separators.join("┼")));
+        for row in 0..1usize << row_vars {
+            out.push_str(&format!("{:>corner_width$} │", label(row, row_vars)));
+            for col in 0..1usize << col_vars {
+                let line = (gray_code(plane as u8) << (row_vars + col_vars)) | (gray_code(row as u8) << col_vars) | gray_code(col as u8);
+                out.push_str(&format!(" {:<width$} │", cell(line)));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    for (i, group) in groups.iter().enumerate() {
+        out.push_str(&format!("{} = {}\n", group_tag(i), ast_to_rpn(&two_level(&[*group], used_char, Disjunction))));
+    }
+    out
+}
 
+fn print_karnaugh_map(formula: &str) {
+    print!("{}", karnaugh_map(formula));
+}
 
-
+//=======================================================================================
 
 
 //========== PARSE THE ASYMETRIC SYNTAX TREE(AST) TO REVERSE POLISH NOTATION(RPN) =======
@@ -490,7 +2373,7 @@ fn main() {
 fn ast_to_rpn(node: &Node) -> String {
     match node {
         Node::Value(val) => val.to_string(),
-        Node::Bool(_c) => panic!("Should not contain '1' or '0'"),
+        Node::Bool(val) => (*val as u8).to_string(),
         Node::UnaryExpr { op, child } => {
             let child_rpn = ast_to_rpn(child);
             format!("{}{}", child_rpn, operator_symbol(op))
@@ -543,7 +2426,6 @@ fn evaluate(node: &Node) -> bool {
     }
 }
 
-#[allow(dead_code)]
 fn give_value_to_char(current_line: i64, formula: &str, used_char: &[char]) -> Node {
     let mut changed_formula: String = formula.to_string();
     let base: i64 = 2;
@@ -1231,7 +3113,6 @@ fn karnaugh_map2(formula: &str, used_char: &mut Vec<char>) -> String{
 }
 
 
-#[allow(dead_code)]
 fn parse_formula_binary(formula: &str) -> Node{
     let mut tree: Vec<Node> = Vec::new();
 
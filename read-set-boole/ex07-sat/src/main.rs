@@ -1,5 +1,8 @@
 // use std::time::Instant;
-#[derive(Debug)]
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fs;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Operator {
     Negation, // ! true now its false and vice versa
     Conjunction, // &
@@ -11,7 +14,7 @@ pub enum Operator {
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Node {
     // leaf
     Value(char),
@@ -37,6 +40,7 @@ fn parse_formula(formula: &str) -> Node{
 
     for c in formula.chars() {
         match c {
+            'A'..='Z' => tree.push(Value(c)),
             '0' => tree.push(Bool(false)),
             '1' => tree.push(Bool(true)),
             '!' =>{
@@ -76,6 +80,38 @@ fn parse_formula(formula: &str) -> Node{
 }
 
 
+//========== PARSE THE ASYMETRIC SYNTAX TREE(AST) TO REVERSE POLISH NOTATION(RPN) =======
+
+fn ast_to_rpn(node: &Node) -> String {
+    match node {
+        Node::Value(val) => val.to_string(),
+        Node::Bool(val) => (*val as u8).to_string(),
+        Node::UnaryExpr { op, child } => {
+            let child_rpn = ast_to_rpn(child);
+            format!("{}{}", child_rpn, operator_symbol(op))
+        }
+        Node::BinaryExpr { op, lhs, rhs } => {
+            let lhs_rpn = ast_to_rpn(lhs);
+            let rhs_rpn = ast_to_rpn(rhs);
+            format!("{}{}{}", lhs_rpn, rhs_rpn, operator_symbol(op))
+        }
+    }
+}
+
+fn operator_symbol(op: &Operator) -> &str {
+    match op {
+        Operator::Negation => "!",
+        Operator::Conjunction => "&",
+        Operator::Disjunction => "|",
+        Operator::ExclusiveDisjunction => "^",
+        Operator::MaterialCondition => ">",
+        Operator::LogicalEquivalence => "=",
+    }
+}
+
+//=======================================================================================
+
+
 fn evaluate(node: &Node) -> bool {
     match node {
         Node::Bool(val) => *val,
@@ -102,6 +138,1598 @@ fn evaluate(node: &Node) -> bool {
     }
 }
 
+//===================================== TSEITIN CNF =====================================
+//=======================================================================================
+
+// A clause is a list of literals, `v` is the variable v and `-v` its negation.
+// Variables 1 to 26 are the letters A to Z, the fresh ones are numbered after them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedCnf {
+    pub clauses: Vec<Vec<i32>>,
+    pub aux: Vec<(i32, Node)>, // fresh variable and the subformula it stands for
+    pub num_vars: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Polarity {
+    Positive,
+    Negative,
+    Both,
+}
+
+impl Polarity {
+    fn flip(self) -> Self {
+        match self {
+            Polarity::Positive => Polarity::Negative,
+            Polarity::Negative => Polarity::Positive,
+            Polarity::Both => Polarity::Both,
+        }
+    }
+}
+
+fn letter_var(c: char) -> i32 {
+    (c as u8 - b'A') as i32 + 1
+}
+
+struct Encoder {
+    cnf: EncodedCnf,
+    polarity_aware: bool,
+}
+
+impl Encoder {
+    fn fresh(&mut self, node: &Node) -> i32 {
+        self.cnf.num_vars += 1;
+        self.cnf.aux.push((self.cnf.num_vars, node.clone()));
+        self.cnf.num_vars
+    }
+
+    // Clauses for x ↔ node, or only the direction the polarity of the node needs
+    // (x → node when it is only seen positively, node → x when only negatively)
+    fn gate(&mut self, polarity: Polarity, implies: Vec<Vec<i32>>, implied: Vec<Vec<i32>>) {
+        let polarity = if self.polarity_aware { polarity } else { Polarity::Both };
+        if polarity != Polarity::Negative {
+            self.cnf.clauses.extend(implies);
+        }
+        if polarity != Polarity::Positive {
+            self.cnf.clauses.extend(implied);
+        }
+    }
+
+    // Literal standing for the node, negations are free and do not need a variable
+    fn encode(&mut self, node: &Node, polarity: Polarity) -> i32 {
+        match node {
+            Node::Value(c) => letter_var(*c),
+            Node::Bool(val) => {
+                let x = self.fresh(node);
+                self.cnf.clauses.push(vec![if *val { x } else { -x }]);
+                x
+            }
+            Node::UnaryExpr { op: _, child } => -self.encode(child, polarity.flip()),
+            Node::BinaryExpr { op, lhs, rhs } => {
+                let (lhs_polarity, rhs_polarity) = match op {
+                    Conjunction | Disjunction => (polarity, polarity),
+                    MaterialCondition => (polarity.flip(), polarity),
+                    _ => (Polarity::Both, Polarity::Both),
+                };
+                let a = self.encode(lhs, lhs_polarity);
+                let b = self.encode(rhs, rhs_polarity);
+                let x = self.fresh(node);
+                match op {
+                    // x ↔ a ∧ b
+                    Conjunction => self.gate(polarity, vec![vec![-x, a], vec![-x, b]], vec![vec![-a, -b, x]]),
+                    // x ↔ a ∨ b
+                    Disjunction => self.gate(polarity, vec![vec![-x, a, b]], vec![vec![-a, x], vec![-b, x]]),
+                    // x ↔ ¬a ∨ b
+                    MaterialCondition => self.gate(polarity, vec![vec![-x, -a, b]], vec![vec![a, x], vec![-b, x]]),
+                    // x ↔ a ⊕ b
+                    ExclusiveDisjunction => self.gate(
+                        polarity,
+                        vec![vec![-x, a, b], vec![-x, -a, -b]],
+                        vec![vec![-a, b, x], vec![a, -b, x]],
+                    ),
+                    // x ↔ (a ↔ b), the same as x ↔ ¬a ⊕ b
+                    LogicalEquivalence => self.gate(
+                        polarity,
+                        vec![vec![-x, -a, b], vec![-x, a, -b]],
+                        vec![vec![a, b, x], vec![-a, -b, x]],
+                    ),
+                    Negation => panic!("Should not enter here"),
+                }
+                x
+            }
+        }
+    }
+}
+
+fn encode_cnf(node: &Node, polarity_aware: bool) -> EncodedCnf {
+    let mut encoder = Encoder { cnf: EncodedCnf { clauses: Vec::new(), aux: Vec::new(), num_vars: 26 }, polarity_aware };
+    let root = encoder.encode(node, Polarity::Positive);
+    encoder.cnf.clauses.push(vec![root]);
+    encoder.cnf
+}
+
+// Equisatisfiable CNF with one fresh variable per operator, linear in the size of the formula
+fn tseitin_cnf(node: &Node) -> EncodedCnf {
+    encode_cnf(node, false)
+}
+
+// Same, but each fresh variable only gets the clauses for the polarity it is used with
+fn plaisted_greenbaum_cnf(node: &Node) -> EncodedCnf {
+    encode_cnf(node, true)
+}
+
+//=======================================================================================
+
+
+//===================================== CLAUSE SETS =====================================
+//=======================================================================================
+
+// Variable numbers follow the encoding: 1 to 26 are the letters A to Z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Literal {
+    pub var: u32,
+    pub negated: bool,
+}
+
+impl Literal {
+    fn from_dimacs(lit: i32) -> Self {
+        Literal { var: lit.unsigned_abs(), negated: lit < 0 }
+    }
+
+    fn to_dimacs(self) -> i32 {
+        if self.negated { -(self.var as i32) } else { self.var as i32 }
+    }
+
+    fn to_node(self) -> Node {
+        assert!((1..=26).contains(&self.var), "Variable {} is not a letter", self.var);
+        let letter = Value((b'A' + (self.var - 1) as u8) as char);
+        if self.negated { UnaryExpr { op: Negation, child: Box::new(letter) } } else { letter }
+    }
+}
+
+// A ∨ of literals
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Clause {
+    pub literals: Vec<Literal>,
+}
+
+// A ∧ of literals
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cube {
+    pub literals: Vec<Literal>,
+}
+
+// A ∧ of clauses, the empty one is true
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cnf {
+    pub clauses: Vec<Clause>,
+}
+
+// A ∨ of cubes, the empty one is false
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dnf {
+    pub cubes: Vec<Cube>,
+}
+
+// Literals of a clause or a cube are kept sorted by variable, positive first, so
+// these only walk them once
+
+fn sort_literals(literals: &mut Vec<Literal>) {
+    literals.sort();
+    literals.dedup();
+}
+
+// A and ¬A both in: a clause that is always true or a cube that is always false
+fn complementary(literals: &[Literal]) -> bool {
+    literals.windows(2).any(|pair| pair[0].var == pair[1].var)
+}
+
+fn subset(small: &[Literal], big: &[Literal]) -> bool {
+    let mut big = big.iter();
+    small.iter().all(|lit| big.any(|other| other == lit))
+}
+
+// Drops the terms holding another one (and so the repeated ones), then sorts
+// them by size and literals
+fn remove_supersets(terms: &mut Vec<Vec<Literal>>) {
+    terms.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    terms.dedup();
+    let mut kept: Vec<Vec<Literal>> = Vec::new();
+    for term in terms.drain(..) {
+        if !kept.iter().any(|other| subset(other, &term)) {
+            kept.push(term);
+        }
+    }
+    *terms = kept;
+}
+
+fn fold_literals(literals: &[Literal], op: Operator, empty: bool) -> Node {
+    literals
+        .iter()
+        .map(|lit| lit.to_node())
+        .reduce(|lhs, rhs| BinaryExpr { op, lhs: Box::new(lhs), rhs: Box::new(rhs) })
+        .unwrap_or(Bool(empty))
+}
+
+fn fold_terms(terms: Vec<Node>, op: Operator, empty: bool) -> Node {
+    terms
+        .into_iter()
+        .reduce(|lhs, rhs| BinaryExpr { op, lhs: Box::new(lhs), rhs: Box::new(rhs) })
+        .unwrap_or(Bool(empty))
+}
+
+// Terms of an NNF formula: the ∧ of `outer` (∧ for the CNF, ∨ for the DNF) unites
+// the sets of terms and the other operator takes every union of one term of each side
+fn nnf_terms(node: &Node, outer: Operator) -> Result<Vec<Vec<Literal>>, String> {
+    match node {
+        Node::Value(c) => Ok(vec![vec![Literal { var: letter_var(*c) as u32, negated: false }]]),
+        Node::UnaryExpr { op: _, child } => match **child {
+            Node::Value(c) => Ok(vec![vec![Literal { var: letter_var(c) as u32, negated: true }]]),
+            _ => Err("Negation above something else than a letter, not in NNF".to_string()),
+        },
+        // 1 is the empty CNF and the DNF with the empty cube, the other way around for 0
+        Node::Bool(val) => Ok(if *val == (outer == Conjunction) { Vec::new() } else { vec![Vec::new()] }),
+        Node::BinaryExpr { op, lhs, rhs } if *op == Conjunction || *op == Disjunction => {
+            let (lhs, rhs) = (nnf_terms(lhs, outer)?, nnf_terms(rhs, outer)?);
+            if *op == outer {
+                Ok(lhs.into_iter().chain(rhs).collect())
+            } else {
+                Ok(lhs.iter().flat_map(|a| rhs.iter().map(move |b| a.iter().chain(b).copied().collect())).collect())
+            }
+        }
+        _ => Err(format!("{} is not allowed in NNF", ast_to_rpn(node))),
+    }
+}
+
+impl Cnf {
+    fn from_node(node: &Node) -> Result<Self, String> {
+        let clauses = nnf_terms(node, Conjunction)?;
+        Ok(Cnf { clauses: clauses.into_iter().map(|literals| Clause { literals }).collect() })
+    }
+
+    fn from_dimacs(clauses: &[Vec<i32>]) -> Self {
+        Cnf {
+            clauses: clauses
+                .iter()
+                .map(|clause| Clause { literals: clause.iter().map(|lit| Literal::from_dimacs(*lit)).collect() })
+                .collect(),
+        }
+    }
+
+    fn to_dimacs(&self) -> Vec<Vec<i32>> {
+        self.clauses.iter().map(|clause| clause.literals.iter().map(|lit| lit.to_dimacs()).collect()).collect()
+    }
+
+    fn remove_duplicate_literals(&mut self) {
+        for clause in &mut self.clauses {
+            sort_literals(&mut clause.literals);
+        }
+    }
+
+    // A ∨ ¬A ∨ ... always holds, the clause says nothing
+    fn remove_tautologies(&mut self) {
+        self.remove_duplicate_literals();
+        self.clauses.retain(|clause| !complementary(&clause.literals));
+    }
+
+    // A clause holding all the literals of another one is implied by it
+    fn remove_subsumed(&mut self) {
+        self.remove_duplicate_literals();
+        let mut terms: Vec<Vec<Literal>> = self.clauses.drain(..).map(|clause| clause.literals).collect();
+        remove_supersets(&mut terms);
+        self.clauses = terms.into_iter().map(|literals| Clause { literals }).collect();
+    }
+
+    fn sort(&mut self) {
+        self.remove_duplicate_literals();
+        self.clauses.sort_by(|a, b| (a.literals.len(), &a.literals).cmp(&(b.literals.len(), &b.literals)));
+    }
+
+    // Same clause set for equal formulas once simplified the same way
+    fn canonicalize(&mut self) {
+        self.remove_tautologies();
+        self.remove_subsumed();
+        self.sort();
+    }
+
+    fn to_node(&self) -> Node {
+        fold_terms(self.clauses.iter().map(|clause| fold_literals(&clause.literals, Disjunction, false)).collect(), Conjunction, true)
+    }
+}
+
+impl Dnf {
+    fn from_node(node: &Node) -> Result<Self, String> {
+        let cubes = nnf_terms(node, Disjunction)?;
+        Ok(Dnf { cubes: cubes.into_iter().map(|literals| Cube { literals }).collect() })
+    }
+
+    fn remove_duplicate_literals(&mut self) {
+        for cube in &mut self.cubes {
+            sort_literals(&mut cube.literals);
+        }
+    }
+
+    // A ∧ ¬A ∧ ... never holds, the cube adds nothing
+    fn remove_contradictions(&mut self) {
+        self.remove_duplicate_literals();
+        self.cubes.retain(|cube| !complementary(&cube.literals));
+    }
+
+    // A cube holding all the literals of another one is absorbed by it
+    fn remove_subsumed(&mut self) {
+        self.remove_duplicate_literals();
+        let mut terms: Vec<Vec<Literal>> = self.cubes.drain(..).map(|cube| cube.literals).collect();
+        remove_supersets(&mut terms);
+        self.cubes = terms.into_iter().map(|literals| Cube { literals }).collect();
+    }
+
+    fn sort(&mut self) {
+        self.remove_duplicate_literals();
+        self.cubes.sort_by(|a, b| (a.literals.len(), &a.literals).cmp(&(b.literals.len(), &b.literals)));
+    }
+
+    fn canonicalize(&mut self) {
+        self.remove_contradictions();
+        self.remove_subsumed();
+        self.sort();
+    }
+
+    fn to_node(&self) -> Node {
+        fold_terms(self.cubes.iter().map(|cube| fold_literals(&cube.literals, Conjunction, true)).collect(), Disjunction, false)
+    }
+}
+
+//=======================================================================================
+
+
+//======================================== DPLL =========================================
+//=======================================================================================
+
+// How DPLL picks the next literal to branch on:
+// Dlis takes the literal found in the most unsatisfied clauses,
+// Moms looks only at the shortest unsatisfied clauses and favours variables found there
+// with both signs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Heuristic {
+    Dlis,
+    Moms,
+}
+
+enum ClauseState {
+    Satisfied,
+    Conflict,
+    Unit(i32),
+    Unresolved,
+}
+
+fn literal_value(values: &[Option<bool>], lit: i32) -> Option<bool> {
+    values[lit.unsigned_abs() as usize].map(|val| val == (lit > 0))
+}
+
+struct Dpll<'a> {
+    clauses: &'a [Vec<i32>],
+    values: Vec<Option<bool>>,
+    trail: Vec<i32>,
+    heuristic: Heuristic,
+}
+
+impl Dpll<'_> {
+    fn assign(&mut self, lit: i32) {
+        self.values[lit.unsigned_abs() as usize] = Some(lit > 0);
+        self.trail.push(lit);
+    }
+
+    fn undo(&mut self, mark: usize) {
+        for lit in self.trail.drain(mark..) {
+            self.values[lit.unsigned_abs() as usize] = None;
+        }
+    }
+
+    fn state(&self, clause: &[i32]) -> ClauseState {
+        let mut free = None;
+        let mut free_count = 0;
+        for lit in clause {
+            match literal_value(&self.values, *lit) {
+                Some(true) => return ClauseState::Satisfied,
+                Some(false) => {}
+                None => {
+                    free = Some(*lit);
+                    free_count += 1;
+                }
+            }
+        }
+        match (free_count, free) {
+            (0, _) => ClauseState::Conflict,
+            (1, Some(lit)) => ClauseState::Unit(lit),
+            _ => ClauseState::Unresolved,
+        }
+    }
+
+    // Unassigned literals of the clauses that are not satisfied yet
+    fn open_clauses(&self) -> Vec<Vec<i32>> {
+        self.clauses
+            .iter()
+            .filter(|clause| !clause.iter().any(|lit| literal_value(&self.values, *lit) == Some(true)))
+            .map(|clause| clause.iter().copied().filter(|lit| literal_value(&self.values, *lit).is_none()).collect())
+            .collect()
+    }
+
+    // Assigns unit and pure literals until neither is left, false on a conflict
+    fn simplify(&mut self) -> bool {
+        loop {
+            let mut changed = false;
+            for clause in self.clauses {
+                match self.state(clause) {
+                    ClauseState::Conflict => return false,
+                    ClauseState::Unit(lit) => {
+                        self.assign(lit);
+                        changed = true;
+                    }
+                    ClauseState::Satisfied | ClauseState::Unresolved => {}
+                }
+            }
+            if changed {
+                continue;
+            }
+            let mut signs: HashMap<u32, (bool, bool)> = HashMap::new();
+            for lit in self.open_clauses().into_iter().flatten() {
+                let sign = signs.entry(lit.unsigned_abs()).or_default();
+                if lit > 0 { sign.0 = true } else { sign.1 = true }
+            }
+            for (var, sign) in signs {
+                if sign.0 != sign.1 {
+                    self.assign(if sign.0 { var as i32 } else { -(var as i32) });
+                    changed = true;
+                }
+            }
+            if !changed {
+                return true;
+            }
+        }
+    }
+
+    fn choose(&self) -> Option<i32> {
+        let open = self.open_clauses();
+        let shortest = open.iter().map(|clause| clause.len()).min()?;
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for clause in open.iter().filter(|clause| self.heuristic == Heuristic::Dlis || clause.len() == shortest) {
+            for lit in clause {
+                *counts.entry(*lit).or_default() += 1;
+            }
+        }
+        let count = |lit: i32| counts.get(&lit).copied().unwrap_or(0);
+        let score = |lit: i32| match self.heuristic {
+            Heuristic::Dlis => count(lit),
+            Heuristic::Moms => (count(lit) + count(-lit)) * 2 + count(lit) * count(-lit),
+        };
+        counts.keys().copied().max_by_key(|lit| (score(*lit), count(*lit), -lit.abs(), *lit))
+    }
+
+    fn search(&mut self) -> bool {
+        let mark = self.trail.len();
+        if !self.simplify() {
+            self.undo(mark);
+            return false;
+        }
+        let Some(lit) = self.choose() else {
+            return true;
+        };
+        let decision = self.trail.len();
+        for branch in [lit, -lit] {
+            self.assign(branch);
+            if self.search() {
+                return true;
+            }
+            self.undo(decision);
+        }
+        self.undo(mark);
+        false
+    }
+}
+
+// Value of every variable from 1 to num_vars in a model (index 0 unused), the ones
+// left free by the search are false
+fn dpll(clauses: &[Vec<i32>], num_vars: i32, heuristic: Heuristic) -> Option<Vec<bool>> {
+    let mut solver = Dpll { clauses, values: vec![None; num_vars as usize + 1], trail: Vec::new(), heuristic };
+    if !solver.search() {
+        return None;
+    }
+    Some(solver.values.iter().map(|val| val.unwrap_or(false)).collect())
+}
+
+//=======================================================================================
+
+
+//========================================= CDCL ========================================
+//=======================================================================================
+
+// Counters of one Cdcl instance, summed over all its solve calls
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SolverStats {
+    pub decisions: u64,
+    pub propagations: u64,
+    pub conflicts: u64,
+    pub restarts: u64,
+    pub learned: u64,
+    pub deleted: u64,
+}
+
+// The first two literals of a clause are the watched ones, and the literal implied by
+// a reason clause is always its first
+struct StoredClause {
+    literals: Vec<i32>,
+    learned: bool,
+    lbd: usize,
+}
+
+// Conflict driven clause learning: two watched literals, 1-UIP learning, EVSIDS with
+// phase saving, Luby restarts and deletion of the learned clauses with the worst LBD
+pub struct Cdcl {
+    clauses: Vec<StoredClause>,
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    level: Vec<usize>,
+    reason: Vec<Option<usize>>,
+    trail: Vec<i32>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    activity: Vec<f64>,
+    var_inc: f64,
+    order: BinaryHeap<(u64, usize)>,
+    phase: Vec<bool>,
+    seen: Vec<bool>,
+    failed: Vec<i32>,
+    max_learned: usize,
+    unsat: bool,
+    stats: SolverStats,
+}
+
+const VAR_DECAY: f64 = 0.95;
+const RESTART_UNIT: u64 = 100;
+
+fn literal_index(lit: i32) -> usize {
+    2 * lit.unsigned_abs() as usize + usize::from(lit < 0)
+}
+
+// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ... for i = 1, 2, 3, ...
+fn luby(mut i: u64) -> u64 {
+    loop {
+        let mut k = 1;
+        while (1 << k) - 1 < i {
+            k += 1;
+        }
+        if (1 << k) - 1 == i {
+            return 1 << (k - 1);
+        }
+        i -= (1 << (k - 1)) - 1;
+    }
+}
+
+impl Cdcl {
+    fn new(num_vars: i32) -> Cdcl {
+        let mut solver = Cdcl {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2],
+            values: vec![None],
+            level: vec![0],
+            reason: vec![None],
+            trail: Vec::new(),
+            trail_lim: Vec::new(),
+            qhead: 0,
+            activity: vec![0.0],
+            var_inc: 1.0,
+            order: BinaryHeap::new(),
+            phase: vec![false],
+            seen: vec![false],
+            failed: Vec::new(),
+            max_learned: 1000,
+            unsat: false,
+            stats: SolverStats::default(),
+        };
+        solver.reserve_vars(num_vars);
+        solver
+    }
+
+    fn reserve_vars(&mut self, num_vars: i32) {
+        for var in self.values.len()..=num_vars.max(0) as usize {
+            self.watches.push(Vec::new());
+            self.watches.push(Vec::new());
+            self.values.push(None);
+            self.level.push(0);
+            self.reason.push(None);
+            self.activity.push(0.0);
+            self.phase.push(false);
+            self.seen.push(false);
+            self.order.push((0, var));
+        }
+    }
+
+    fn value(&self, lit: i32) -> Option<bool> {
+        literal_value(&self.values, lit)
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    fn enqueue(&mut self, lit: i32, reason: Option<usize>) {
+        let var = lit.unsigned_abs() as usize;
+        self.values[var] = Some(lit > 0);
+        self.level[var] = self.decision_level();
+        self.reason[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        for lit in self.trail.drain(self.trail_lim[level]..) {
+            let var = lit.unsigned_abs() as usize;
+            self.values[var] = None;
+            self.reason[var] = None;
+            self.phase[var] = lit > 0;
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+        self.trail_lim.truncate(level);
+        self.qhead = self.trail.len();
+    }
+
+    fn watch(&mut self, index: usize) {
+        let literals = &self.clauses[index].literals;
+        self.watches[literal_index(literals[0])].push(index);
+        self.watches[literal_index(literals[1])].push(index);
+    }
+
+    // Drops duplicate literals, tautologies and what level 0 already decides, the
+    // clause holds until the solver is dropped
+    fn add_clause(&mut self, clause: &[i32]) {
+        self.backtrack(0);
+        let mut literals = clause.to_vec();
+        literals.sort_by_key(|lit| (lit.abs(), *lit));
+        literals.dedup();
+        self.reserve_vars(literals.iter().map(|lit| lit.abs()).max().unwrap_or(0));
+        if self.unsat || literals.windows(2).any(|pair| pair[0] == -pair[1]) || literals.iter().any(|lit| self.value(*lit) == Some(true)) {
+            return;
+        }
+        literals.retain(|lit| self.value(*lit).is_none());
+        match literals.len() {
+            0 => self.unsat = true,
+            1 => self.enqueue(literals[0], None),
+            _ => {
+                self.clauses.push(StoredClause { literals, learned: false, lbd: 0 });
+                self.watch(self.clauses.len() - 1);
+            }
+        }
+    }
+
+    // Index of the falsified clause on a conflict
+    fn propagate(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let false_lit = -self.trail[self.qhead];
+            self.qhead += 1;
+            self.stats.propagations += 1;
+            let mut watchers = std::mem::take(&mut self.watches[literal_index(false_lit)]);
+            let mut i = 0;
+            let mut conflict = None;
+            while i < watchers.len() {
+                let index = watchers[i];
+                let literals = &mut self.clauses[index].literals;
+                if literals[0] == false_lit {
+                    literals.swap(0, 1);
+                }
+                let first = literals[0];
+                if literal_value(&self.values, first) == Some(true) {
+                    i += 1;
+                    continue;
+                }
+                if let Some(k) = (2..literals.len()).find(|k| literal_value(&self.values, literals[*k]) != Some(false)) {
+                    literals.swap(1, k);
+                    self.watches[literal_index(literals[1])].push(index);
+                    watchers.swap_remove(i);
+                    continue;
+                }
+                if literal_value(&self.values, first) == Some(false) {
+                    conflict = Some(index);
+                    break;
+                }
+                self.enqueue(first, Some(index));
+                i += 1;
+            }
+            self.watches[literal_index(false_lit)].append(&mut watchers);
+            if conflict.is_some() {
+                self.qhead = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump(&mut self, var: usize) {
+        self.activity[var] += self.var_inc;
+        if self.activity[var] > 1e100 {
+            for activity in &mut self.activity {
+                *activity *= 1e-100;
+            }
+            self.var_inc *= 1e-100;
+            self.order = (1..self.values.len())
+                .filter(|var| self.values[*var].is_none())
+                .map(|var| (self.activity[var].to_bits(), var))
+                .collect();
+        }
+        if self.values[var].is_none() {
+            self.order.push((self.activity[var].to_bits(), var));
+        }
+    }
+
+    // Walks the trail back from the conflict until a single literal of the current level
+    // is left, the learned clause starts with its negation and has the literal of the
+    // level to jump back to in second place
+    fn analyze(&mut self, conflict: usize) -> (Vec<i32>, usize) {
+        let mut learned = vec![0];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut skip = 0;
+        let uip = loop {
+            let literals = std::mem::take(&mut self.clauses[clause].literals);
+            for lit in &literals[skip..] {
+                let var = lit.unsigned_abs() as usize;
+                if !self.seen[var] && self.level[var] > 0 {
+                    self.seen[var] = true;
+                    self.bump(var);
+                    if self.level[var] == self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learned.push(*lit);
+                    }
+                }
+            }
+            self.clauses[clause].literals = literals;
+            loop {
+                index -= 1;
+                if self.seen[self.trail[index].unsigned_abs() as usize] {
+                    break;
+                }
+            }
+            let lit = self.trail[index];
+            let var = lit.unsigned_abs() as usize;
+            self.seen[var] = false;
+            pending -= 1;
+            if pending == 0 {
+                break lit;
+            }
+            clause = self.reason[var].expect("Implied literal without a reason");
+            skip = 1;
+        };
+        learned[0] = -uip;
+        for lit in &learned[1..] {
+            self.seen[lit.unsigned_abs() as usize] = false;
+        }
+        let mut jump = 0;
+        if let Some(k) = (1..learned.len()).max_by_key(|k| self.level[learned[*k].unsigned_abs() as usize]) {
+            learned.swap(1, k);
+            jump = self.level[learned[1].unsigned_abs() as usize];
+        }
+        (learned, jump)
+    }
+
+    fn learn(&mut self, learned: Vec<i32>) {
+        self.stats.learned += 1;
+        if learned.len() == 1 {
+            self.enqueue(learned[0], None);
+            return;
+        }
+        let mut levels: Vec<usize> = learned.iter().map(|lit| self.level[lit.unsigned_abs() as usize]).collect();
+        levels.sort();
+        levels.dedup();
+        let asserting = learned[0];
+        self.clauses.push(StoredClause { literals: learned, learned: true, lbd: levels.len() });
+        self.watch(self.clauses.len() - 1);
+        self.enqueue(asserting, Some(self.clauses.len() - 1));
+    }
+
+    // Called at level 0 only, where no reason is ever read again, so the clause indices
+    // can change freely
+    fn reduce_learned(&mut self) {
+        let mut learned: Vec<usize> = (0..self.clauses.len()).filter(|index| self.clauses[*index].learned).collect();
+        if learned.len() <= self.max_learned {
+            return;
+        }
+        learned.sort_by_key(|index| std::cmp::Reverse((self.clauses[*index].lbd, self.clauses[*index].literals.len())));
+        let mut delete = vec![false; self.clauses.len()];
+        for index in &learned[..learned.len() / 2] {
+            if self.clauses[*index].lbd > 2 {
+                delete[*index] = true;
+                self.stats.deleted += 1;
+            }
+        }
+        let mut index = 0;
+        self.clauses.retain(|_| {
+            index += 1;
+            !delete[index - 1]
+        });
+        for reason in &mut self.reason {
+            *reason = None;
+        }
+        for watchers in &mut self.watches {
+            watchers.clear();
+        }
+        for index in 0..self.clauses.len() {
+            self.watch(index);
+        }
+        self.qhead = 0;
+        self.max_learned += self.max_learned / 10;
+    }
+
+    fn pick_branch(&mut self) -> Option<i32> {
+        while let Some((bits, var)) = self.order.pop() {
+            if self.values[var].is_none() && bits == self.activity[var].to_bits() {
+                return Some(if self.phase[var] { var as i32 } else { -(var as i32) });
+            }
+        }
+        None
+    }
+
+    // Assumptions the false literal p depends on, p included: walks the trail back
+    // through the reasons down to the decisions, which are all assumptions here
+    fn analyze_final(&mut self, p: i32) -> Vec<i32> {
+        let mut failed = vec![p];
+        if self.level[p.unsigned_abs() as usize] == 0 {
+            return failed;
+        }
+        self.seen[p.unsigned_abs() as usize] = true;
+        for index in (self.trail_lim[0]..self.trail.len()).rev() {
+            let lit = self.trail[index];
+            let var = lit.unsigned_abs() as usize;
+            if !self.seen[var] {
+                continue;
+            }
+            match self.reason[var] {
+                None => failed.push(lit),
+                Some(reason) => {
+                    for other in &self.clauses[reason].literals[1..] {
+                        if self.level[other.unsigned_abs() as usize] > 0 {
+                            self.seen[other.unsigned_abs() as usize] = true;
+                        }
+                    }
+                }
+            }
+            self.seen[var] = false;
+        }
+        failed
+    }
+
+    // Value of every variable from 1 to num_vars in a model (index 0 unused)
+    fn solve(&mut self) -> Option<Vec<bool>> {
+        self.solve_with(&[])
+    }
+
+    // Same, with the assumptions taken as the first decisions. On None, failed holds the
+    // assumptions that clash with the clauses, empty when the clauses alone do
+    fn solve_with(&mut self, assumptions: &[i32]) -> Option<Vec<bool>> {
+        self.backtrack(0);
+        self.reserve_vars(assumptions.iter().map(|lit| lit.abs()).max().unwrap_or(0));
+        self.failed.clear();
+        let mut restarts = 0;
+        let mut conflicts = 0;
+        let mut limit = luby(1) * RESTART_UNIT;
+        while !self.unsat {
+            if let Some(conflict) = self.propagate() {
+                self.stats.conflicts += 1;
+                conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.unsat = true;
+                    break;
+                }
+                let (learned, jump) = self.analyze(conflict);
+                self.backtrack(jump);
+                self.learn(learned);
+                self.var_inc /= VAR_DECAY;
+            } else if conflicts >= limit {
+                restarts += 1;
+                conflicts = 0;
+                limit = luby(restarts + 1) * RESTART_UNIT;
+                self.stats.restarts += 1;
+                self.backtrack(0);
+                self.reduce_learned();
+            } else if let Some(&assumption) = assumptions.get(self.decision_level()) {
+                // An assumption already true still opens its level, so that level i
+                // keeps standing for assumption i
+                match self.value(assumption) {
+                    Some(false) => {
+                        self.failed = self.analyze_final(assumption);
+                        return None;
+                    }
+                    Some(true) => self.trail_lim.push(self.trail.len()),
+                    None => {
+                        self.trail_lim.push(self.trail.len());
+                        self.enqueue(assumption, None);
+                    }
+                }
+            } else if let Some(lit) = self.pick_branch() {
+                self.stats.decisions += 1;
+                self.trail_lim.push(self.trail.len());
+                self.enqueue(lit, None);
+            } else {
+                return Some(self.values.iter().map(|val| val.unwrap_or(false)).collect());
+            }
+        }
+        None
+    }
+}
+
+fn cdcl(clauses: &[Vec<i32>], num_vars: i32) -> Option<Vec<bool>> {
+    let mut solver = Cdcl::new(num_vars);
+    for clause in clauses {
+        solver.add_clause(clause);
+    }
+    solver.solve()
+}
+
+// The solver behind sat_dimacs
+fn solve_clauses(clauses: &[Vec<i32>], num_vars: i32) -> bool {
+    cdcl(clauses, num_vars).is_some()
+}
+
+//=======================================================================================
+
+
+//======================================== DIMACS =======================================
+//=======================================================================================
+
+// A clause set as DIMACS has it, variable v is called names[v - 1]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dimacs {
+    pub names: Vec<String>,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+// Renumbers the variables 1, 2, 3... in the order they show up
+fn number_variables(clauses: &[Vec<i32>], name: impl Fn(i32) -> String) -> Dimacs {
+    let mut seen: Vec<i32> = Vec::new();
+    let clauses = clauses
+        .iter()
+        .map(|clause| {
+            clause
+                .iter()
+                .map(|lit| {
+                    let index = match seen.iter().position(|var| *var == lit.abs()) {
+                        Some(index) => index,
+                        None => {
+                            seen.push(lit.abs());
+                            seen.len() - 1
+                        }
+                    };
+                    (index as i32 + 1) * lit.signum()
+                })
+                .collect()
+        })
+        .collect();
+    Dimacs { names: seen.into_iter().map(name).collect(), clauses }
+}
+
+// From the RPN conjunctive_normal_form gives, like AB|C!&
+fn cnf_to_dimacs(cnf: &str) -> Dimacs {
+    fn clauses(node: &Node, out: &mut Vec<Vec<i32>>) {
+        match node {
+            Node::BinaryExpr { op: Conjunction, lhs, rhs } => {
+                clauses(lhs, out);
+                clauses(rhs, out);
+            }
+            Node::Bool(true) => {}
+            clause => {
+                let mut literals = Vec::new();
+                literals_of(clause, &mut literals);
+                out.push(literals);
+            }
+        }
+    }
+    fn literals_of(node: &Node, out: &mut Vec<i32>) {
+        match node {
+            Node::BinaryExpr { op: Disjunction, lhs, rhs } => {
+                literals_of(lhs, out);
+                literals_of(rhs, out);
+            }
+            Node::Value(c) => out.push(letter_var(*c)),
+            Node::UnaryExpr { op: _, child } => match **child {
+                Node::Value(c) => out.push(-letter_var(c)),
+                _ => panic!("Not in CNF"),
+            },
+            Node::Bool(false) => {}
+            _ => panic!("Not in CNF"),
+        }
+    }
+    let mut out = Vec::new();
+    clauses(&parse_formula(cnf), &mut out);
+    number_variables(&out, |var| ((b'A' + (var - 1) as u8) as char).to_string())
+}
+
+// The fresh variables of the encoding are called x27, x28...
+fn encoded_to_dimacs(cnf: &EncodedCnf) -> Dimacs {
+    number_variables(&cnf.clauses, |var| {
+        if var <= 26 { ((b'A' + (var - 1) as u8) as char).to_string() } else { format!("x{var}") }
+    })
+}
+
+fn write_dimacs(dimacs: &Dimacs) -> String {
+    let mut out = String::new();
+    for (i, name) in dimacs.names.iter().enumerate() {
+        out.push_str(&format!("c var {} {name}\n", i + 1));
+    }
+    out.push_str(&format!("p cnf {} {}\n", dimacs.names.len(), dimacs.clauses.len()));
+    for clause in &dimacs.clauses {
+        for lit in clause {
+            out.push_str(&format!("{lit} "));
+        }
+        out.push_str("0\n");
+    }
+    out
+}
+
+// Clauses can span lines, and the SATLIB files end with a % line. Without
+// `c var` comments the variables are named after their number
+fn read_dimacs(text: &str) -> Result<Dimacs, String> {
+    let mut header: Option<(usize, usize)> = None;
+    let mut names: Vec<(usize, String)> = Vec::new();
+    let mut clauses = Vec::new();
+    let mut clause = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => continue,
+            ["%", ..] => break,
+            ["c", "var", var, name] => {
+                let var = var.parse().map_err(|_| format!("line {}: invalid variable {var}", number + 1))?;
+                names.push((var, name.to_string()));
+            }
+            ["c", ..] => continue,
+            ["p", "cnf", vars, count] => {
+                if header.is_some() {
+                    return Err(format!("line {}: second p line", number + 1));
+                }
+                let vars = vars.parse().map_err(|_| format!("line {}: invalid variable count", number + 1))?;
+                let count = count.parse().map_err(|_| format!("line {}: invalid clause count", number + 1))?;
+                header = Some((vars, count));
+            }
+            ["p", ..] => return Err(format!("line {}: expected p cnf <variables> <clauses>", number + 1)),
+            literals => {
+                let (vars, _) = header.ok_or(format!("line {}: clause before the p line", number + 1))?;
+                for word in literals {
+                    let lit: i32 = word.parse().map_err(|_| format!("line {}: invalid literal {word}", number + 1))?;
+                    if lit == 0 {
+                        clauses.push(std::mem::take(&mut clause));
+                    } else if lit.unsigned_abs() as usize > vars {
+                        return Err(format!("line {}: variable {} above the {vars} of the p line", number + 1, lit.abs()));
+                    } else {
+                        clause.push(lit);
+                    }
+                }
+            }
+        }
+    }
+    let (vars, count) = header.ok_or("missing p line")?;
+    if !clause.is_empty() {
+        return Err("last clause is not terminated by 0".to_string());
+    }
+    if clauses.len() != count {
+        return Err(format!("p line announces {count} clauses, found {}", clauses.len()));
+    }
+    let mut all_names: Vec<String> = (1..=vars).map(|var| var.to_string()).collect();
+    for (var, name) in names {
+        if var == 0 || var > vars {
+            return Err(format!("name given to variable {var}, outside of the p line"));
+        }
+        all_names[var - 1] = name;
+    }
+    Ok(Dimacs { names: all_names, clauses })
+}
+
+fn load_dimacs(path: &str) -> Result<Dimacs, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    read_dimacs(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+fn benchmark_path(file: &str) -> String {
+    format!("{}/benchmarks/{file}", env!("CARGO_MANIFEST_DIR"))
+}
+
+// Needs letters for the variables: their names if they are letters, else A, B, C...
+fn dimacs_to_node(dimacs: &Dimacs) -> Result<Node, String> {
+    let lettered = dimacs.names.iter().all(|name| name.len() == 1 && name.chars().all(|c| c.is_ascii_uppercase()));
+    if !lettered && dimacs.names.len() > 26 {
+        return Err(format!("{} variables do not fit in the letters A to Z", dimacs.names.len()));
+    }
+    let letter = |lit: i32| {
+        let var = lit.unsigned_abs() as usize;
+        let c = if lettered { dimacs.names[var - 1].chars().next().unwrap() } else { (b'A' + (var - 1) as u8) as char };
+        if lit < 0 { UnaryExpr { op: Negation, child: Box::new(Value(c)) } } else { Value(c) }
+    };
+    let clause = |clause: &Vec<i32>| {
+        clause
+            .iter()
+            .map(|lit| letter(*lit))
+            .reduce(|lhs, rhs| BinaryExpr { op: Disjunction, lhs: Box::new(lhs), rhs: Box::new(rhs) })
+            .unwrap_or(Bool(false))
+    };
+    Ok(dimacs
+        .clauses
+        .iter()
+        .map(clause)
+        .reduce(|lhs, rhs| BinaryExpr { op: Conjunction, lhs: Box::new(lhs), rhs: Box::new(rhs) })
+        .unwrap_or(Bool(true)))
+}
+
+fn sat_dimacs(dimacs: &Dimacs) -> bool {
+    solve_clauses(&dimacs.clauses, dimacs.names.len() as i32)
+}
+
+//=======================================================================================
+
+
+//======================================== MODELS =======================================
+//=======================================================================================
+
+// Value of every letter of a formula, in the order they appear in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    pub values: Vec<(char, bool)>,
+}
+
+impl Model {
+    fn value(&self, c: char) -> Option<bool> {
+        self.values.iter().find(|(letter, _)| *letter == c).map(|(_, val)| *val)
+    }
+
+    fn assign(&self, node: &Node) -> Node {
+        match node {
+            Value(c) => Bool(self.value(*c).unwrap_or_else(|| panic!("{c} is missing from the model"))),
+            Bool(val) => Bool(*val),
+            UnaryExpr { op, child } => UnaryExpr { op: *op, child: Box::new(self.assign(child)) },
+            BinaryExpr { op, lhs, rhs } => {
+                BinaryExpr { op: *op, lhs: Box::new(self.assign(lhs)), rhs: Box::new(self.assign(rhs)) }
+            }
+        }
+    }
+
+    // Checks the model against the formula itself rather than its CNF
+    fn satisfies(&self, formula: &str) -> bool {
+        evaluate(&self.assign(&parse_formula(formula)))
+    }
+
+    // Header and one line of the truth table of the formula
+    fn print(&self, formula: &str) {
+        for (c, _) in &self.values {
+            print!("| {c} ");
+        }
+        println!("| = |");
+        for _i in 0..=self.values.len() {
+            print!("|---");
+        }
+        println!("|");
+        for (_, val) in &self.values {
+            print!("| {} ", u8::from(*val));
+        }
+        println!("| {} |", u8::from(self.satisfies(formula)));
+    }
+}
+
+fn solve(formula: &str) -> Option<Model> {
+    let cnf = tseitin_cnf(&parse_formula(formula));
+    let values = cdcl(&cnf.clauses, cnf.num_vars)?;
+    let values = parse_formula_char(formula).into_iter().map(|c| (c, values[letter_var(c) as usize])).collect();
+    Some(Model { values })
+}
+
+//=======================================================================================
+
+
+//==================================== MODEL COUNTING ===================================
+//=======================================================================================
+
+// Arbitrary size natural number, base 2^32 digits from the lowest, no trailing zero
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigUint {
+    digits: Vec<u32>,
+}
+
+impl BigUint {
+    fn from_u64(val: u64) -> BigUint {
+        let mut num = BigUint { digits: vec![val as u32, (val >> 32) as u32] };
+        num.trim();
+        num
+    }
+
+    fn trim(&mut self) {
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    fn add(&self, other: &BigUint) -> BigUint {
+        let mut digits = Vec::new();
+        let mut carry = 0u64;
+        for i in 0..self.digits.len().max(other.digits.len()) {
+            let sum = carry + *self.digits.get(i).unwrap_or(&0) as u64 + *other.digits.get(i).unwrap_or(&0) as u64;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        digits.push(carry as u32);
+        let mut num = BigUint { digits };
+        num.trim();
+        num
+    }
+
+    fn mul(&self, other: &BigUint) -> BigUint {
+        let mut digits = vec![0u32; self.digits.len() + other.digits.len()];
+        for (i, lhs) in self.digits.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, rhs) in other.digits.iter().enumerate() {
+                let product = *lhs as u64 * *rhs as u64 + digits[i + j] as u64 + carry;
+                digits[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            digits[i + other.digits.len()] = carry as u32;
+        }
+        let mut num = BigUint { digits };
+        num.trim();
+        num
+    }
+
+    // self * 2^bits
+    fn shl(&self, bits: usize) -> BigUint {
+        if self.is_zero() {
+            return BigUint::default();
+        }
+        let mut digits = vec![0u32; bits / 32];
+        let mut carry = 0u32;
+        for digit in &self.digits {
+            let wide = (*digit as u64) << (bits % 32);
+            digits.push(wide as u32 | carry);
+            carry = (wide >> 32) as u32;
+        }
+        digits.push(carry);
+        let mut num = BigUint { digits };
+        num.trim();
+        num
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Repeated division by 10^9 gives the decimal digits nine at a time
+        let mut digits = self.digits.clone();
+        let mut chunks = Vec::new();
+        while !digits.is_empty() {
+            let mut rest = 0u64;
+            for digit in digits.iter_mut().rev() {
+                let current = (rest << 32) | *digit as u64;
+                *digit = (current / 1_000_000_000) as u32;
+                rest = current % 1_000_000_000;
+            }
+            chunks.push(rest);
+            while digits.last() == Some(&0) {
+                digits.pop();
+            }
+        }
+        match chunks.split_last() {
+            None => write!(f, "0"),
+            Some((high, rest)) => {
+                write!(f, "{high}")?;
+                rest.iter().rev().try_for_each(|chunk| write!(f, "{chunk:09}"))
+            }
+        }
+    }
+}
+
+// Every model of a formula once, over its letters or over the given projection: each
+// model found is blocked by a clause before asking the solver for the next one
+pub struct Models {
+    solver: Cdcl,
+    letters: Vec<char>,
+}
+
+impl Iterator for Models {
+    type Item = Model;
+
+    fn next(&mut self) -> Option<Model> {
+        let values = self.solver.solve()?;
+        let model = Model { values: self.letters.iter().map(|c| (*c, values[letter_var(*c) as usize])).collect() };
+        let blocking: Vec<i32> =
+            model.values.iter().map(|(c, val)| if *val { -letter_var(*c) } else { letter_var(*c) }).collect();
+        self.solver.add_clause(&blocking);
+        Some(model)
+    }
+}
+
+fn models(formula: &str, projection: Option<&[char]>) -> Models {
+    let cnf = tseitin_cnf(&parse_formula(formula));
+    let mut solver = Cdcl::new(cnf.num_vars);
+    for clause in &cnf.clauses {
+        solver.add_clause(clause);
+    }
+    let letters = projection.map(|letters| letters.to_vec()).unwrap_or_else(|| parse_formula_char(formula));
+    Models { solver, letters }
+}
+
+fn variable_count(clauses: &[Vec<i32>]) -> usize {
+    let mut vars: Vec<u32> = clauses.iter().flatten().map(|lit| lit.unsigned_abs()).collect();
+    vars.sort();
+    vars.dedup();
+    vars.len()
+}
+
+// Clauses left once the literals and the units they lead to are true, sorted so that
+// equal sets look the same to the cache, and the number of variables fixed on the
+// way. None when a clause becomes false
+fn propagate_units(clauses: &[Vec<i32>], lits: &[i32]) -> Option<(Vec<Vec<i32>>, usize)> {
+    let mut assigned: HashMap<u32, bool> = lits.iter().map(|lit| (lit.unsigned_abs(), *lit > 0)).collect();
+    let mut current = clauses.to_vec();
+    loop {
+        let mut next = Vec::new();
+        let mut units = Vec::new();
+        for clause in &current {
+            let value = |lit: &i32| assigned.get(&lit.unsigned_abs()).map(|val| *val == (*lit > 0));
+            if clause.iter().any(|lit| value(lit) == Some(true)) {
+                continue;
+            }
+            let rest: Vec<i32> = clause.iter().copied().filter(|lit| value(lit).is_none()).collect();
+            match rest.len() {
+                0 => return None,
+                1 => units.push(rest[0]),
+                _ => {}
+            }
+            next.push(rest);
+        }
+        current = next;
+        if units.is_empty() {
+            break;
+        }
+        for unit in units {
+            if assigned.insert(unit.unsigned_abs(), unit > 0) == Some(unit < 0) {
+                return None;
+            }
+        }
+    }
+    current.sort();
+    current.dedup();
+    Some((current, assigned.len()))
+}
+
+// Clause sets that share no variable, each one sorted like its parent
+fn components(clauses: &[Vec<i32>]) -> Vec<Vec<Vec<i32>>> {
+    let mut occurrences: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (index, clause) in clauses.iter().enumerate() {
+        for lit in clause {
+            occurrences.entry(lit.unsigned_abs()).or_default().push(index);
+        }
+    }
+    let mut visited = vec![false; clauses.len()];
+    let mut result = Vec::new();
+    for start in 0..clauses.len() {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut stack = vec![start];
+        let mut members = Vec::new();
+        while let Some(index) = stack.pop() {
+            members.push(index);
+            for lit in &clauses[index] {
+                for other in &occurrences[&lit.unsigned_abs()] {
+                    if !visited[*other] {
+                        visited[*other] = true;
+                        stack.push(*other);
+                    }
+                }
+            }
+        }
+        members.sort();
+        result.push(members.into_iter().map(|index| clauses[index].clone()).collect());
+    }
+    result
+}
+
+// Models of the clauses over their own variables once the literals are set, the
+// variables that vanish without being fixed count twice
+fn count_with(clauses: &[Vec<i32>], lits: &[i32], cache: &mut HashMap<Vec<Vec<i32>>, BigUint>) -> BigUint {
+    let Some((reduced, fixed)) = propagate_units(clauses, lits) else {
+        return BigUint::default();
+    };
+    let free = variable_count(clauses) - variable_count(&reduced) - fixed;
+    count_components(&reduced, cache).shl(free)
+}
+
+// Splits into independent components and multiplies their counts, a single component
+// branches on its most frequent variable
+fn count_components(clauses: &[Vec<i32>], cache: &mut HashMap<Vec<Vec<i32>>, BigUint>) -> BigUint {
+    if clauses.is_empty() {
+        return BigUint::from_u64(1);
+    }
+    if let Some(count) = cache.get(clauses) {
+        return count.clone();
+    }
+    let parts = components(clauses);
+    let count = if parts.len() > 1 {
+        let mut count = BigUint::from_u64(1);
+        for part in &parts {
+            count = count.mul(&count_components(part, cache));
+            if count.is_zero() {
+                break;
+            }
+        }
+        count
+    } else {
+        let mut occurrences: HashMap<u32, usize> = HashMap::new();
+        for lit in clauses.iter().flatten() {
+            *occurrences.entry(lit.unsigned_abs()).or_default() += 1;
+        }
+        let var = occurrences.into_iter().max_by_key(|(var, count)| (*count, std::cmp::Reverse(*var))).map(|(var, _)| var as i32);
+        let var = var.expect("Component without variables");
+        count_with(clauses, &[var], cache).add(&count_with(clauses, &[-var], cache))
+    };
+    cache.insert(clauses.to_vec(), count.clone());
+    count
+}
+
+// Models over num_vars variables, the ones the clauses leave out are free
+fn count_clauses(clauses: &[Vec<i32>], num_vars: i32) -> BigUint {
+    let mut normalized: Vec<Vec<i32>> = Vec::new();
+    for clause in clauses {
+        let mut literals = clause.clone();
+        literals.sort_by_key(|lit| (lit.abs(), *lit));
+        literals.dedup();
+        if !literals.windows(2).any(|pair| pair[0] == -pair[1]) {
+            normalized.push(literals);
+        }
+    }
+    normalized.sort();
+    normalized.dedup();
+    let free = num_vars as usize - variable_count(&normalized);
+    count_with(&normalized, &[], &mut HashMap::new()).shl(free)
+}
+
+fn count_dimacs(dimacs: &Dimacs) -> BigUint {
+    count_clauses(&dimacs.clauses, dimacs.names.len() as i32)
+}
+
+// Models over the letters of the formula. Every auxiliary variable of the Tseitin
+// encoding is fixed by the letters, so the count of the clauses is the same
+fn count_models(formula: &str) -> BigUint {
+    let cnf = tseitin_cnf(&parse_formula(formula));
+    count_clauses(&cnf.clauses, variable_count(&cnf.clauses) as i32)
+}
+
+//=======================================================================================
+
+
+//===================================== UNSAT CORES =====================================
+//=======================================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreMinimization {
+    Off,      // the constraints of the final conflict, small but not always minimal
+    Deletion, // then drops every constraint the others are still unsatisfiable without
+}
+
+// Labels of a subset of the constraints that is still unsatisfiable, in their order,
+// None when they all hold together. Each constraint is only asserted by assuming the
+// literal of its root, so the solver can tell which ones it used
+fn unsat_core<'a>(constraints: &[(&'a str, Node)], minimization: CoreMinimization) -> Option<Vec<&'a str>> {
+    let mut encoder =
+        Encoder { cnf: EncodedCnf { clauses: Vec::new(), aux: Vec::new(), num_vars: 26 }, polarity_aware: false };
+    let roots: Vec<i32> = constraints.iter().map(|(_, node)| encoder.encode(node, Polarity::Positive)).collect();
+    let mut solver = Cdcl::new(encoder.cnf.num_vars);
+    for clause in &encoder.cnf.clauses {
+        solver.add_clause(clause);
+    }
+    if solver.solve_with(&roots).is_some() {
+        return None;
+    }
+    let mut core = solver.failed.clone();
+    if minimization == CoreMinimization::Deletion {
+        let mut necessary: Vec<i32> = Vec::new();
+        while let Some(candidate) = core.pop() {
+            let assumptions: Vec<i32> = necessary.iter().chain(&core).copied().collect();
+            if solver.solve_with(&assumptions).is_some() {
+                necessary.push(candidate);
+            } else {
+                core = solver.failed.iter().copied().filter(|lit| !necessary.contains(lit)).collect();
+            }
+        }
+        core = necessary;
+    }
+    // Constraints with the same root share a literal, the first one stands for all
+    let mut labels = Vec::new();
+    let mut taken = Vec::new();
+    for ((label, _), root) in constraints.iter().zip(&roots) {
+        if core.contains(root) && !taken.contains(root) {
+            taken.push(*root);
+            labels.push(*label);
+        }
+    }
+    Some(labels)
+}
+
+//=======================================================================================
+
+
+//================================== INCREMENTAL SOLVER =================================
+//=======================================================================================
+
+// One Cdcl kept across calls, so what it learned carries over. The Tseitin
+// definitions of a formula always hold, only the clause asserting its root belongs to
+// the open scope: it gets the negation of the scope's activation variable, which
+// every call assumes until pop sets it false for good
+pub struct IncrementalSolver {
+    cdcl: Cdcl,
+    encoder: Encoder,
+    scopes: Vec<i32>,
+    letters: Vec<char>,
+}
+
+impl IncrementalSolver {
+    fn new() -> IncrementalSolver {
+        IncrementalSolver {
+            cdcl: Cdcl::new(26),
+            encoder: Encoder {
+                cnf: EncodedCnf { clauses: Vec::new(), aux: Vec::new(), num_vars: 26 },
+                polarity_aware: false,
+            },
+            scopes: Vec::new(),
+            letters: Vec::new(),
+        }
+    }
+
+    fn add_letters(&mut self, letters: impl IntoIterator<Item = char>) {
+        for c in letters {
+            if !self.letters.contains(&c) {
+                self.letters.push(c);
+            }
+        }
+    }
+
+    fn add(&mut self, formula: &str) {
+        self.add_letters(parse_formula_char(formula));
+        let root = self.encoder.encode(&parse_formula(formula), Polarity::Positive);
+        self.encoder.cnf.aux.clear();
+        for clause in std::mem::take(&mut self.encoder.cnf.clauses) {
+            self.cdcl.add_clause(&clause);
+        }
+        match self.scopes.last() {
+            Some(activation) => self.cdcl.add_clause(&[root, -activation]),
+            None => self.cdcl.add_clause(&[root]),
+        }
+    }
+
+    fn push(&mut self) {
+        self.encoder.cnf.num_vars += 1;
+        self.scopes.push(self.encoder.cnf.num_vars);
+    }
+
+    // Forgets what was added since the matching push
+    fn pop(&mut self) {
+        let activation = self.scopes.pop().expect("pop without a matching push");
+        self.cdcl.add_clause(&[-activation]);
+    }
+
+    // A model over every letter seen so far, with the assumed letters set as asked
+    fn solve(&mut self, assumptions: &[(char, bool)]) -> Option<Model> {
+        self.add_letters(assumptions.iter().map(|(c, _)| *c));
+        let mut lits = self.scopes.clone();
+        lits.extend(assumptions.iter().map(|(c, val)| if *val { letter_var(*c) } else { -letter_var(*c) }));
+        let values = self.cdcl.solve_with(&lits)?;
+        Some(Model { values: self.letters.iter().map(|c| (*c, values[letter_var(*c) as usize])).collect() })
+    }
+}
+
+//=======================================================================================
+
+
 fn parse_formula_char(formula: &str) ->  Vec<char> {
     let mut tree: Vec<Node> = Vec::new();
     let mut used_char: Vec<char> = Vec::new();
@@ -114,6 +1742,9 @@ fn parse_formula_char(formula: &str) ->  Vec<char> {
                     used_char.push(c);
                 }
             }
+            '0' | '1' => {
+                continue;
+            }
             '!' =>{
                 continue;
             }
@@ -152,7 +1783,8 @@ fn give_value_to_char(current_line: i64, formula: &str, used_char: &[char]) -> N
     parse_formula(changed_formula.as_str())
 }
 
-fn sat(formula: &str) -> bool{
+// Tries every line of the truth table, kept to check the solver against
+fn sat_brute_force(formula: &str) -> bool{
     let used_char = parse_formula_char(formula);
     let base = 2i64;
     let mut sati:bool = false;
@@ -167,6 +1799,18 @@ fn sat(formula: &str) -> bool{
     sati
 }
 
+// Same loop, counting the lines where the formula is true
+fn count_brute_force(formula: &str) -> u64 {
+    let used_char = parse_formula_char(formula);
+    (0..1i64 << used_char.len()).filter(|i| evaluate(&give_value_to_char(*i, formula, &used_char))).count() as u64
+}
+
+fn sat(formula: &str) -> bool {
+    let mut solver = IncrementalSolver::new();
+    solver.add(formula);
+    solver.solve(&[]).is_some()
+}
+
 fn main() {
     println!("{}", sat("AB|"));
     // true
@@ -176,6 +1820,160 @@ fn main() {
     // false
     println!("{}", sat("AA^"));
     // false
+    println!("{}", sat("A1&B|"));
+    // true
+    println!("{}", sat("A0&"));
+    // false
+    println!("{}", sat("AB&C|DE&^FG|^HI&^JK=&LM>|"));
+    // true
+    println!("{}", sat("AB^BC^&CA^&"));
+    // false
+
+    for formula in ["AB|", "AA!&", "AB=A!B=&", "AB>BC>&AC>!&", "AB^C^AB&C|!&", "A1>0=", "AB&!A!B!|>"] {
+        assert_eq!(sat(formula), sat_brute_force(formula), "{formula}");
+        let cnf = plaisted_greenbaum_cnf(&parse_formula(formula));
+        assert_eq!(solve_clauses(&cnf.clauses, cnf.num_vars), sat_brute_force(formula), "{formula}");
+    }
+
+    println!();
+    let dimacs = cnf_to_dimacs("A!B!C!||AB|&C&");
+    print!("{}", write_dimacs(&dimacs));
+    // c var 1 A
+    // c var 2 B
+    // c var 3 C
+    // p cnf 3 3
+    // -1 -2 -3 0
+    // 1 2 0
+    // 3 0
+    assert_eq!(read_dimacs(&write_dimacs(&dimacs)), Ok(dimacs.clone()));
+    println!("{}", ast_to_rpn(&dimacs_to_node(&dimacs).expect("Too many variables")));
+    // A!B!|C!|AB|&C&
+    println!("{}", sat_dimacs(&encoded_to_dimacs(&tseitin_cnf(&parse_formula("AB^BC^&CA^&")))));
+    // false
+    let php = load_dimacs(&benchmark_path("php-4-3.cnf")).expect("Could not load the benchmark");
+    println!("{} variables, {} clauses, {}", php.names.len(), php.clauses.len(), sat_dimacs(&php));
+    // 12 variables, 22 clauses, false
+    println!("{:?}", dpll(&php.clauses, php.names.len() as i32, Heuristic::Moms));
+    // None
+    let php = load_dimacs(&benchmark_path("php-7-6.cnf")).expect("Could not load the benchmark");
+    let mut solver = Cdcl::new(php.names.len() as i32);
+    for clause in &php.clauses {
+        solver.add_clause(clause);
+    }
+    println!("{:?}", solver.solve());
+    // None
+    println!("{:?}", solver.stats);
+    // SolverStats { decisions: 895, propagations: 9176, conflicts: 726, restarts: 5, learned: 725, deleted: 0 }
+
+    println!();
+    let cnf = tseitin_cnf(&parse_formula("AB^BC^&"));
+    for heuristic in [Heuristic::Dlis, Heuristic::Moms] {
+        let model = dpll(&cnf.clauses, cnf.num_vars, heuristic).expect("AB^BC^& is satisfiable");
+        println!("{:?}: A = {}, B = {}, C = {}", heuristic, model[1], model[2], model[3]);
+    }
+    // Dlis: A = false, B = true, C = false
+    // Moms: A = false, B = true, C = false
+    let model = cdcl(&cnf.clauses, cnf.num_vars).expect("AB^BC^& is satisfiable");
+    println!("Cdcl: A = {}, B = {}, C = {}", model[1], model[2], model[3]);
+    // Cdcl: A = false, B = true, C = false
+
+    println!();
+    let model = solve("AB>BC>&CA!&&").expect("AB>BC>&CA!&& is satisfiable");
+    model.print("AB>BC>&CA!&&");
+    // | A | B | C | = |
+    // |---|---|---|---|
+    // | 0 | 0 | 1 | 1 |
+    assert!(model.satisfies("AB>BC>&CA!&&"));
+    println!("{:?}", solve("AB>BC>&CA!&&A&"));
+    // None
+    println!("{:?}", solve("AB^C&"));
+    // Some(Model { values: [('A', true), ('B', false), ('C', true)] })
+
+    println!();
+    for model in models("AB|C&", None) {
+        println!("{:?}", model.values);
+    }
+    // [('A', true), ('B', false), ('C', true)]
+    // [('A', true), ('B', true), ('C', true)]
+    // [('A', false), ('B', true), ('C', true)]
+    println!("{}", models("AB|C&", Some(&['A'])).count());
+    // 2
+    println!("{} {}", count_models("AB|C&"), count_models("AB&C|DE&^FG|^HI&^JK=&LM>|"));
+    // 3 6640
+    for formula in ["AB|", "AA!&", "AB=A!B=&", "AB>BC>&AC>!&", "AB^C^AB&C|!&", "A1>0=", "AB&!A!B!|>"] {
+        assert_eq!(count_models(formula).to_string(), count_brute_force(formula).to_string(), "{formula}");
+    }
+    let pairs: Vec<Vec<i32>> = (0..50).map(|i| vec![2 * i + 1, 2 * i + 2]).collect();
+    println!("{}", count_clauses(&pairs, 100));
+    // 717897987691852588770249
+    let path: Vec<Vec<i32>> = (1..100).map(|i| vec![-i, -(i + 1)]).collect();
+    println!("{}", count_clauses(&path, 100));
+    // 927372692193078999176
+    println!("{}", count_dimacs(&php));
+    // 0
+
+    println!();
+    let constraints: Vec<(&str, Node)> = [
+        ("sunroof", "S"),
+        ("sunroof needs the premium trim", "SP>"),
+        ("premium trim needs leather", "PL>"),
+        ("diesel or electric", "DE|"),
+        ("no leather with the eco pack", "LC&!"),
+        ("eco pack", "C"),
+        ("electric comes with the eco pack", "EC>"),
+    ]
+    .into_iter()
+    .map(|(label, formula)| (label, parse_formula(formula)))
+    .collect();
+    println!("{:?}", unsat_core(&constraints, CoreMinimization::Off));
+    // Some(["sunroof", "sunroof needs the premium trim", "premium trim needs leather", "no leather with the eco pack", "eco pack"])
+    println!("{:?}", unsat_core(&constraints, CoreMinimization::Deletion));
+    // Some(["sunroof", "sunroof needs the premium trim", "premium trim needs leather", "no leather with the eco pack", "eco pack"])
+    println!("{:?}", unsat_core(&constraints[..5], CoreMinimization::Deletion));
+    // None
+
+    println!();
+    let mut solver = IncrementalSolver::new();
+    solver.add("SP>");
+    solver.add("PL>");
+    solver.add("LC&!");
+    println!("{}", solver.solve(&[('S', true), ('C', true)]).is_some());
+    // false
+    println!("{:?}", solver.solve(&[('S', true)]).map(|model| model.values));
+    // Some([('S', true), ('P', true), ('L', true), ('C', false)])
+    solver.push();
+    solver.add("C");
+    println!("{}", solver.solve(&[('S', true)]).is_some());
+    // false
+    println!("{:?}", solver.solve(&[]).map(|model| model.values));
+    // Some([('S', false), ('P', false), ('L', false), ('C', true)])
+    solver.pop();
+    println!("{}", solver.solve(&[('S', true)]).is_some());
+    // true
+
+    println!();
+    let mut cnf = Cnf::from_node(&parse_formula("AB|A!A|&BA|B|&B&AB!|&")).expect("Not in NNF");
+    cnf.canonicalize();
+    println!("{}", ast_to_rpn(&cnf.to_node()));
+    // BAB!|&
+    let mut dnf = Dnf::from_node(&parse_formula("AB&A!&AB&C&|A|")).expect("Not in NNF");
+    dnf.canonicalize();
+    println!("{}", ast_to_rpn(&dnf.to_node()));
+    // A
+    let mut cnf = Cnf::from_node(&parse_formula("AB&C|")).expect("Not in NNF");
+    cnf.canonicalize();
+    println!("{}", ast_to_rpn(&cnf.to_node()));
+    // AC|BC|&
+    let mut dnf = Dnf::from_node(&parse_formula("AB&C|")).expect("Not in NNF");
+    dnf.canonicalize();
+    println!("{}", ast_to_rpn(&dnf.to_node()));
+    // CAB&|
+    println!("{}", Cnf::from_node(&parse_formula("AB^")).unwrap_err());
+    // AB^ is not allowed in NNF
+    let mut cnf = Cnf::from_dimacs(&[vec![2, -1, 2], vec![1, -1], vec![-1, 2, 3]]);
+    cnf.canonicalize();
+    println!("{:?}", cnf.to_dimacs());
+    // [[-1, 2]]
 }
 
 #[cfg(debug_assertions)]